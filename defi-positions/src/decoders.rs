@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use zephyr_sdk::{
+    soroban_sdk::{
+        self, contracttype,
+        xdr::{ScVal, ScVec},
+        Address, Map, Symbol,
+    },
+    EnvClient,
+};
+
+use crate::{utils::address_to_strkey, DataKey};
+
+/// Summary of a position held in a protocol. Amounts are in the protocol's
+/// own units, e.g. b/d-token shares for Blend and pool shares for xycloans.
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct Position {
+    /// Strkey (or FxDAO denomination) of the asset, when the protocol exposes it.
+    pub asset: Option<String>,
+    pub supplied: i128,
+    pub borrowed: i128,
+    pub collateral: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Positions {
+    pub liabilities: Map<u32, i128>,
+    pub collateral: Map<u32, i128>,
+    pub supply: Map<u32, i128>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum PoolDataKey {
+    Positions(Address),
+}
+
+enum Kind {
+    Blend { reserves: Vec<String> },
+    Xycloans { token: Option<String> },
+    FxDao,
+}
+
+/// Decodes the entries of a single scanned contract. Built once per contract
+/// since some layouts need contract-wide data (e.g. Blend's reserve list).
+pub struct Decoder {
+    kind: Kind,
+}
+
+impl Decoder {
+    /// Returns `None` for protocols we don't know the storage layout of.
+    pub fn new(env: &EnvClient, protocol: &str, entries: &[(ScVal, ScVal)]) -> Option<Self> {
+        let kind = match protocol {
+            "Blend" => Kind::Blend {
+                reserves: match find_value(env, entries, "ResList") {
+                    Some(ScVal::Vec(Some(ScVec(reserves)))) => reserves
+                        .iter()
+                        .map(|reserve| address_to_strkey(reserve).unwrap_or_default())
+                        .collect(),
+                    _ => Vec::new(),
+                },
+            },
+            "xycloans" => Kind::Xycloans {
+                token: find_value(env, entries, "TokenId").and_then(address_to_strkey),
+            },
+            "FxDAO" => Kind::FxDao,
+            _ => return None,
+        };
+
+        Some(Self { kind })
+    }
+
+    /// Positions held in a matched entry, `None` if the entry isn't a
+    /// position. Errors when the key is a position's but the value doesn't
+    /// have the expected layout.
+    pub fn decode(&self, env: &EnvClient, key: &ScVal, value: &ScVal) -> Result<Option<Vec<Position>>, String> {
+        match &self.kind {
+            Kind::Blend { reserves } => {
+                if env.try_from_scval::<PoolDataKey>(key).is_err() {
+                    return Ok(None);
+                }
+                let positions: Positions = env
+                    .try_from_scval(value)
+                    .map_err(|_| "Blend positions don't match the expected layout".to_string())?;
+
+                let mut by_reserve: BTreeMap<u32, Position> = BTreeMap::new();
+                for (index, amount) in positions.supply.iter() {
+                    reserve_position(&mut by_reserve, reserves, index).supplied += amount;
+                }
+                for (index, amount) in positions.liabilities.iter() {
+                    reserve_position(&mut by_reserve, reserves, index).borrowed += amount;
+                }
+                for (index, amount) in positions.collateral.iter() {
+                    reserve_position(&mut by_reserve, reserves, index).collateral += amount;
+                }
+
+                Ok(Some(by_reserve.into_values().collect()))
+            }
+
+            Kind::Xycloans { token } => {
+                if env.try_from_scval::<DataKey>(key).is_err() {
+                    return Ok(None);
+                }
+                let supplied: i128 = env
+                    .try_from_scval(value)
+                    .map_err(|_| "xycloans balance is not an i128".to_string())?;
+
+                Ok(Some(vec![Position {
+                    asset: token.clone(),
+                    supplied,
+                    ..Default::default()
+                }]))
+            }
+
+            // Vaults are read field by field as their layout changed across
+            // FxDAO deployments.
+            Kind::FxDao => {
+                let ScVal::Map(Some(fields)) = value else {
+                    return Ok(None);
+                };
+                let field = |name: &str| {
+                    let name = env.to_scval(Symbol::new(env.soroban(), name));
+                    fields.iter().find(|entry| entry.key == name).map(|entry| &entry.val)
+                };
+                let amount = |name: &str| -> Result<u128, String> {
+                    let val = field(name).ok_or(format!("FxDAO vault has no {}", name))?;
+                    env.try_from_scval(val).map_err(|_| format!("FxDAO vault {} is not a u128", name))
+                };
+
+                if field("total_debt").is_none() {
+                    return Ok(None);
+                }
+                let borrowed = amount("total_debt")?;
+                let collateral = amount("total_collateral")?;
+                let asset = match field("denomination") {
+                    Some(ScVal::Symbol(denomination)) => denomination.to_utf8_string().ok(),
+                    _ => None,
+                };
+
+                Ok(Some(vec![Position {
+                    asset,
+                    borrowed: borrowed as i128,
+                    collateral: collateral as i128,
+                    ..Default::default()
+                }]))
+            }
+        }
+    }
+}
+
+fn reserve_position<'a>(by_reserve: &'a mut BTreeMap<u32, Position>, reserves: &[String], index: u32) -> &'a mut Position {
+    by_reserve.entry(index).or_insert_with(|| Position {
+        asset: reserves.get(index as usize).cloned(),
+        ..Default::default()
+    })
+}
+
+/// Value stored under `name`, either as its own entry or in the instance
+/// storage, with the key being a bare symbol or a unit enum variant.
+fn find_value<'a>(env: &EnvClient, entries: &'a [(ScVal, ScVal)], name: &str) -> Option<&'a ScVal> {
+    let symbol = env.to_scval(Symbol::new(env.soroban(), name));
+    let variant = ScVal::Vec(Some(ScVec([symbol.clone()].try_into().unwrap())));
+    let matches = |key: &ScVal| *key == symbol || *key == variant;
+
+    for (key, value) in entries {
+        if matches(key) {
+            return Some(value);
+        }
+
+        if let ScVal::ContractInstance(instance) = value {
+            if let Some(storage) = &instance.storage {
+                if let Some(entry) = storage.iter().find(|entry| matches(&entry.key)) {
+                    return Some(&entry.val);
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -1,30 +1,175 @@
+use decoders::{Decoder, Position};
 use serde::{Deserialize, Serialize};
 use utils::find_address_in_scval;
-use zephyr_sdk::{prelude::*, soroban_sdk::{self, contracttype, xdr::ScVal, Address}, EnvClient};
+use zephyr_sdk::{prelude::*, soroban_sdk::{self, contracttype, xdr::{ContractExecutable, LedgerEntryData, ScVal}, Address, String as SorString, TryFromVal, Val}, EnvClient};
 
+mod decoders;
 mod utils;
 
 #[derive(Deserialize)]
 pub struct Request {
     /// Address to scan.
-    address: String,
+    address: Option<String>,
+
+    /// Addresses to scan, together with `address`.
+    #[serde(default)]
+    addresses: Vec<String>,
 
     /// Additional contracts to scan.
     additional: Option<Vec<(String, String)>>,
+
+    /// Additional tokens to read the balances of.
+    tokens: Option<Vec<String>>,
+
+    /// Include the raw key and value of every found entry.
+    #[serde(default)]
+    raw: bool,
 }
 
 #[derive(Deserialize, Serialize)]
-pub struct Found {
-    protocol: String,
+pub struct RawEntry {
     key: ScVal,
     value: ScVal
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct Found {
+    protocol: String,
+    contract: String,
+    positions: Vec<Position>,
+
+    /// Always present for entries that couldn't be decoded into positions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<RawEntry>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The requested address is not a valid account or contract strkey.
+    InvalidAddress,
+    /// A protocol contract strkey is invalid.
+    InvalidContract,
+    /// The contract's entries couldn't be read.
+    UnreadableContract,
+    /// An entry couldn't be decoded, its raw key and value are returned.
+    DecodingFailed,
+    /// A Stellar Asset Contract balance can't be read for an account, as SAC
+    /// balances of `G` accounts live in account and trustline entries.
+    BalancesUnavailable,
+}
+
+#[derive(Serialize)]
+pub struct Error {
+    code: ErrorCode,
+    message: String,
+
+    /// Contract the error refers to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contract: Option<String>,
+
+    /// Requested address the error refers to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+}
+
+impl Error {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            contract: None,
+            address: None,
+        }
+    }
+
+    fn contract(mut self, contract: String) -> Self {
+        self.contract = Some(contract);
+        self
+    }
+
+    fn address(mut self, address: String) -> Self {
+        self.address = Some(address);
+        self
+    }
+}
+
+#[derive(Serialize)]
+pub struct TokenBalance {
+    token: String,
+    balance: i128,
+}
+
+#[derive(Serialize)]
+pub struct AddressPositions {
+    address: String,
+    found: Vec<Found>,
+
+    /// Non-zero balances of the scanned tokens.
+    balances: Vec<TokenBalance>,
+}
+
+#[derive(Serialize)]
+pub struct Positions {
+    /// One group per valid requested address, in request order.
+    addresses: Vec<AddressPositions>,
+
+    /// Problems that didn't prevent the rest of the scan.
+    errors: Vec<Error>,
+}
+
+/// Envelope of every response: `result` is set when `ok`, `error` otherwise.
+#[derive(Serialize)]
+pub struct Response<T> {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<T>,
+}
+
+impl<T> Response<T> {
+    fn ok(result: T) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            result: Some(result),
+        }
+    }
+
+    fn error(error: Error) -> Self {
+        Self {
+            ok: false,
+            error: Some(error),
+            result: None,
+        }
+    }
+}
+
 #[contracttype]
 enum DataKey {
     Balance(Address)
 }
 
+/// Balance entry layout of Stellar Asset Contracts, SEP-41 tokens built
+/// with the token SDK store a bare `i128` instead.
+#[contracttype]
+struct BalanceValue {
+    amount: i128,
+    authorized: bool,
+    clawback: bool,
+}
+
+/// Tokens whose balances are always read. Both are SACs, so they're only
+/// read for contract holders, see `ErrorCode::BalancesUnavailable`.
+const DEFAULT_TOKENS: [&str; 2] = [
+    // XLM
+    "CAS3J7GYLGXMF6TDJBBYYSE3HQ6BBSMLNUQ34T6TZMYMW2EVH34XOWMA",
+
+    // USDC
+    "CCW67TSZV3SSS2HXMBQ5JFGCKJNXKZM7UQUWUZPUTHXSTZLEO7SJMI75",
+];
+
 const DEFAULT_SCAN: [(&'static str, &'static str); 5] = [
     // XycLoans
     ("xycloans", "CBV4OSTRMD2IJJYX3XRNIIVCNA5B2ZLHQMUEUJSKLAH45ONANQ2QV7QN"),
@@ -43,11 +188,29 @@ pub extern "C" fn get_positions() {
     let env = EnvClient::empty();
     let body: Request = env.read_request_body();
 
-    let address_id = match stellar_strkey::Strkey::from_string(&body.address).unwrap() {
-        stellar_strkey::Strkey::Contract(contract) => contract.0,
-        stellar_strkey::Strkey::PublicKeyEd25519(publkey) => publkey.0,
-        _ => panic!() // or return error response
-    };
+    let mut requested: Vec<String> = body.address.into_iter().collect();
+    requested.extend(body.addresses);
+
+    let mut errors: Vec<Error> = Vec::new();
+    // Keeps the requested address for the response next to its ed25519 key
+    // (or contract hash) and canonical strkey. Addresses with the same
+    // canonical strkey, like an `M` address and its `G` account, share a group.
+    let mut groups: Vec<(AddressPositions, [u8; 32], String)> = Vec::new();
+    for address in requested {
+        match parse_address(&address) {
+            Ok((id, canonical)) => {
+                if !groups.iter().any(|(_, _, scanned)| *scanned == canonical) {
+                    groups.push((AddressPositions { address, found: Vec::new(), balances: Vec::new() }, id, canonical))
+                }
+            }
+            Err(error) => errors.push(error.address(address)),
+        }
+    }
+
+    if groups.is_empty() {
+        let error = errors.pop().unwrap_or_else(|| Error::new(ErrorCode::InvalidAddress, "no address to scan"));
+        return env.conclude(Response::<Positions>::error(error));
+    }
 
     let mut additional_protocols = if let Some(additional) = body.additional {
         additional
@@ -57,25 +220,146 @@ pub extern "C" fn get_positions() {
 
     let mut all_protocols: Vec<(String, String)> = DEFAULT_SCAN.to_vec().iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
     all_protocols.append(&mut additional_protocols);
-    
-    let mut found: Vec<Found> = Vec::new();
-
-    for contract in DEFAULT_SCAN {
-        let id = stellar_strkey::Contract::from_string(&contract.1).unwrap().0;
-        
-        let entries = env.read_contract_entries(id).unwrap();
-        for entry in entries {
-            if find_address_in_scval(&entry.key, address_id) {
-                let zephyr_sdk::soroban_sdk::xdr::LedgerEntryData::ContractData(data) = entry.entry.data else { panic!() };
-                let found_entry = Found {
-                    protocol: contract.0.to_string(),
-                    key: entry.key,
-                    value: data.val
+
+    let mut to_scan: Vec<(String, [u8; 32])> = Vec::new();
+    for (protocol, contract) in all_protocols {
+        match stellar_strkey::Contract::from_string(&contract) {
+            Ok(id) => {
+                if !to_scan.iter().any(|(_, scanned)| *scanned == id.0) {
+                    to_scan.push((protocol, id.0))
+                }
+            }
+            Err(_) => errors.push(Error::new(ErrorCode::InvalidContract, format!("invalid {} contract", protocol)).contract(contract)),
+        }
+    }
+
+    // Each contract is read once and its entries matched against all the
+    // requested addresses.
+    for (protocol, id) in to_scan {
+        let contract = stellar_strkey::Contract(id).to_string();
+        let Ok(contract_entries) = env.read_contract_entries(id) else {
+            errors.push(Error::new(ErrorCode::UnreadableContract, format!("couldn't read {} entries", protocol)).contract(contract));
+            continue;
+        };
+
+        let mut entries: Vec<(ScVal, ScVal)> = Vec::new();
+        for entry in contract_entries {
+            if let LedgerEntryData::ContractData(data) = entry.entry.data {
+                entries.push((entry.key, data.val))
+            } else {
+                errors.push(Error::new(ErrorCode::DecodingFailed, "entry is not contract data").contract(contract.clone()));
+            }
+        }
+        let decoder = Decoder::new(&env, &protocol, &entries);
+
+        for (key, value) in entries.iter() {
+            let matching: Vec<usize> = (0..groups.len()).filter(|idx| find_address_in_scval(key, groups[*idx].1)).collect();
+            if matching.is_empty() {
+                continue;
+            }
+
+            let positions = match decoder.as_ref().map(|decoder| decoder.decode(&env, key, value)) {
+                Some(Ok(positions)) => positions,
+                Some(Err(message)) => {
+                    errors.push(Error::new(ErrorCode::DecodingFailed, message).contract(contract.clone()));
+                    None
+                }
+                None => None,
+            };
+
+            for idx in matching {
+                let raw = if body.raw || positions.is_none() {
+                    Some(RawEntry { key: key.clone(), value: value.clone() })
+                } else {
+                    None
                 };
-                found.push(found_entry)
+
+                groups[idx].0.found.push(Found {
+                    protocol: protocol.clone(),
+                    contract: contract.clone(),
+                    positions: positions.clone().unwrap_or_default(),
+                    raw
+                })
             }
         }
     }
-    
-    env.conclude(&found)
+
+    let mut tokens: Vec<String> = DEFAULT_TOKENS.iter().map(|token| token.to_string()).collect();
+    for token in body.tokens.unwrap_or_default() {
+        if stellar_strkey::Contract::from_string(&token).is_err() {
+            errors.push(Error::new(ErrorCode::InvalidContract, "invalid token contract").contract(token));
+        } else if !tokens.contains(&token) {
+            tokens.push(token)
+        }
+    }
+
+    for token in tokens {
+        let token_id = stellar_strkey::Contract::from_string(&token).unwrap().0;
+        let Some(is_sac) = is_sac(&env, token_id) else {
+            errors.push(Error::new(ErrorCode::UnreadableContract, "couldn't read token instance").contract(token.clone()));
+            continue;
+        };
+
+        for (group, _, canonical) in groups.iter_mut() {
+            if is_sac && stellar_strkey::Contract::from_string(canonical).is_err() {
+                errors.push(Error::new(ErrorCode::BalancesUnavailable, "SAC balances are only read for contract addresses").contract(token.clone()).address(group.address.clone()));
+                continue;
+            }
+            let holder = Address::from_string(&SorString::from_str(env.soroban(), canonical));
+            let balance = match env.read_contract_entry_by_key::<DataKey, Val>(token_id, DataKey::Balance(holder)) {
+                Ok(Some(value)) => token_balance(&env, value),
+                Ok(None) => continue,
+                Err(_) => {
+                    errors.push(Error::new(ErrorCode::UnreadableContract, "couldn't read token balance").contract(token.clone()).address(group.address.clone()));
+                    continue;
+                }
+            };
+
+            match balance {
+                Some(0) => {}
+                Some(balance) => group.balances.push(TokenBalance { token: token.clone(), balance }),
+                None => errors.push(Error::new(ErrorCode::DecodingFailed, "unknown token balance layout").contract(token.clone()).address(group.address.clone())),
+            }
+        }
+    }
+
+    let addresses = groups.into_iter().map(|(group, _, _)| group).collect();
+    env.conclude(Response::ok(Positions { addresses, errors }))
+}
+
+/// Whether `token` is a Stellar Asset Contract, `None` when its instance
+/// can't be read.
+fn is_sac(env: &EnvClient, token: [u8; 32]) -> Option<bool> {
+    let instance = env.read_contract_instance(token).ok()??;
+    let LedgerEntryData::ContractData(data) = instance.entry.data else {
+        return None;
+    };
+    let ScVal::ContractInstance(instance) = data.val else {
+        return None;
+    };
+
+    Some(instance.executable == ContractExecutable::StellarAsset)
+}
+
+fn token_balance(env: &EnvClient, value: Val) -> Option<i128> {
+    if let Ok(balance) = i128::try_from_val(env.soroban(), &value) {
+        return Some(balance);
+    }
+
+    BalanceValue::try_from_val(env.soroban(), &value).ok().map(|balance| balance.amount)
+}
+
+/// Key matched against the entries and canonical strkey of an address. Muxed
+/// accounts are normalized to their underlying `G` account, as that's the
+/// address protocols and tokens store.
+fn parse_address(address: &str) -> Result<([u8; 32], String), Error> {
+    match stellar_strkey::Strkey::from_string(address) {
+        Ok(stellar_strkey::Strkey::Contract(contract)) => Ok((contract.0, contract.to_string())),
+        Ok(stellar_strkey::Strkey::PublicKeyEd25519(publkey)) => Ok((publkey.0, publkey.to_string())),
+        Ok(stellar_strkey::Strkey::MuxedAccountEd25519(muxed)) => {
+            Ok((muxed.ed25519, stellar_strkey::ed25519::PublicKey(muxed.ed25519).to_string()))
+        }
+        Ok(_) => Err(Error::new(ErrorCode::InvalidAddress, "only account, muxed account and contract addresses are supported")),
+        Err(_) => Err(Error::new(ErrorCode::InvalidAddress, format!("{} is not a valid strkey", address))),
+    }
 }
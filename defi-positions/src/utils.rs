@@ -1,6 +1,19 @@
 use zephyr_sdk::soroban_sdk::xdr::{PublicKey, ScAddress, ScVal};
 
+/// Strkey of an account or contract address, `None` for any other value.
+pub fn address_to_strkey(val: &ScVal) -> Option<String> {
+    match val {
+        ScVal::Address(ScAddress::Account(account)) => {
+            let PublicKey::PublicKeyTypeEd25519(pubkey) = &account.0;
+            Some(stellar_strkey::ed25519::PublicKey(pubkey.0).to_string())
+        }
+        ScVal::Address(ScAddress::Contract(hash)) => Some(stellar_strkey::Contract(hash.0).to_string()),
+        _ => None,
+    }
+}
 
+/// Whether `address` (an ed25519 key or contract hash) appears in `val`.
+/// Muxed accounts are matched by passing their underlying ed25519 key.
 pub fn find_address_in_scval(val: &ScVal, address: [u8; 32]) -> bool {
     match val {
         ScVal::Address(object) => match object {
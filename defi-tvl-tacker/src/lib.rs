@@ -1,30 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use zephyr_sdk::{
     charting::{Dashboard, DashboardBuilder},
     prelude::*,
     soroban_sdk::{
+        self, contracttype,
         xdr::{
-            AccountId, ContractExecutable, LedgerEntryData, LedgerFootprint, LedgerKey,
-            LedgerKeyContractCode, LedgerKeyContractData, MuxedAccount, Operation, OperationBody,
-            PublicKey, RestoreFootprintOp, ScAddress, ScSymbol, ScVal, ScVec, SequenceNumber,
-            Transaction, TransactionEnvelope, TransactionExt, TransactionV1Envelope, Uint256,
+            AccountId, AlphaNum12, AlphaNum4, Asset, AssetCode12, AssetCode4, ContractIdPreimage,
+            Hash, HashIdPreimage, HashIdPreimageContractId, Int128Parts, LedgerEntryData,
+            PublicKey, ScAddress, ScSymbol, ScVal, Uint256,
         },
-        Address, Symbol,
+        String as SorobanString, Symbol,
     },
-    utils::{address_to_alloc_string, sign_transaction},
+    utils::{sha256, soroban_string_to_alloc_string},
     DatabaseDerive, EnvClient,
 };
 
-#[derive(DatabaseDerive, Clone)]
-#[with_name("defiopids")]
-pub struct Ids {
-    current: u32,
-    contract: String,
-    asset: String,
-}
-
 #[derive(DatabaseDerive, Clone)]
 #[with_name("sacdefiop")]
 pub struct SACDefiOperation {
@@ -41,134 +33,334 @@ pub struct SACDefiOperation {
     id: u32,
 }
 
-fn get_current_state_for_target(
-    env: &EnvClient,
-    asset: String,
+// Latest count, volume and TVL of a target for a SAC. `sacdefiop` keeps the
+// history, this table only the current values.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("defistate")]
+pub struct SACDefiState {
+    sac: String,
     target: String,
-) -> (u32, u32, i128, i128) {
-    let ids_iter = env
-        .read_filter()
-        .column_equal_to("contract", target.clone())
-        .column_equal_to("asset", asset.clone())
-        .read()
-        .unwrap();
-    let (first_time, id): (bool, Ids) = if let Some(id) = ids_iter.get(0).cloned() {
-        (false, id)
-    } else {
-        (
-            true,
-            Ids {
-                current: 0,
-                contract: target.clone(),
-                asset: asset.clone(),
-            },
-        )
-    };
+    t_count: u32,
+    t_volume: i128,
+    t_tvl: i128,
+}
 
-    if first_time {
-        env.put(&Ids {
-            current: id.current + 1,
-            contract: id.contract.clone(),
-            asset: id.asset.clone(),
-        });
-    } else {
-        env.update()
-            .column_equal_to("contract", target.clone())
-            .column_equal_to("asset", asset.clone())
-            .execute(&Ids {
-                current: id.current + 1,
-                contract: target.clone(),
-                asset: asset.clone(),
-            })
-            .unwrap();
+// Running totals recorded in `sacdefiop`, to seed `defistate` with the
+// values from before it existed.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("sacdefiop")]
+pub struct SACDefiTotals {
+    sac: String,
+    target: String,
+    t_count: u32,
+    t_volume: i128,
+    t_tvl: i128,
+    id: u32,
+}
+
+impl From<SACDefiTotals> for SACDefiState {
+    fn from(totals: SACDefiTotals) -> Self {
+        Self {
+            sac: totals.sac,
+            target: totals.target,
+            t_count: totals.t_count,
+            t_volume: totals.t_volume,
+            t_tvl: totals.t_tvl,
+        }
     }
+}
 
-    let previous_iter = env
+/// State of a target from its latest `sacdefiop` row, for targets without a
+/// `defistate` row yet.
+fn seed(env: &EnvClient, sac: &str, target: &str) -> Option<SACDefiState> {
+    let rows: Vec<SACDefiTotals> = env
         .read_filter()
-        .column_equal_to("target", target)
-        .column_equal_to("sac", asset)
-        .column_equal_to("id", id.current)
+        .column_equal_to("sac", sac.to_string())
+        .column_equal_to("target", target.to_string())
         .read()
         .unwrap();
-    let previous: Option<&SACDefiOperation> = previous_iter.last();
-
-    if let Some(previous) = previous {
-        (
-            id.current,
-            previous.t_count,
-            previous.t_tvl,
-            previous.t_volume,
-        )
-    } else {
-        (0, 0, 0, 0)
+
+    rows.into_iter().max_by_key(|row| row.id).map(Into::into)
+}
+
+/// States touched during the current close. Each state is read once and
+/// written back once in `flush`, so that several operations hitting the same
+/// target within a ledger build on each other.
+struct StateCache {
+    states: HashMap<(String, String), (bool, SACDefiState)>,
+}
+
+impl StateCache {
+    fn new() -> Self {
+        Self {
+            states: HashMap::new(),
+        }
     }
+
+    fn get_mut(&mut self, env: &EnvClient, sac: &str, target: &str) -> &mut SACDefiState {
+        let (_, state) = self
+            .states
+            .entry((sac.to_string(), target.to_string()))
+            .or_insert_with(|| {
+                let rows: Vec<SACDefiState> = env
+                    .read_filter()
+                    .column_equal_to("sac", sac.to_string())
+                    .column_equal_to("target", target.to_string())
+                    .read()
+                    .unwrap();
+
+                if let Some(state) = rows.into_iter().next() {
+                    (true, state)
+                } else {
+                    let state = seed(env, sac, target).unwrap_or_else(|| SACDefiState {
+                        sac: sac.to_string(),
+                        target: target.to_string(),
+                        t_count: 0,
+                        t_volume: 0,
+                        t_tvl: 0,
+                    });
+                    (false, state)
+                }
+            });
+
+        state
+    }
+
+    fn flush(self, env: &EnvClient) {
+        for (_, (exists, state)) in self.states {
+            if exists {
+                env.update()
+                    .column_equal_to("sac", state.sac.clone())
+                    .column_equal_to("target", state.target.clone())
+                    .execute(&state)
+                    .unwrap();
+            } else {
+                env.put(&state);
+            }
+        }
+    }
+}
+
+/// Protocol contracts whose SAC flows are tracked. Transfers between
+/// any other addresses are ignored.
+const TRACKED_PROTOCOLS: [&str; 5] = [
+    // XycLoans
+    "CBV4OSTRMD2IJJYX3XRNIIVCNA5B2ZLHQMUEUJSKLAH45ONANQ2QV7QN",
+    // FxDAO
+    "CCUN4RXU5VNDHSF4S4RKV4ZJYMX2YWKOH6L4AKEKVNVDQ7HY5QIAO4UB",
+    "CDCART6WRSM2K4CKOAOB5YKUVBSJ6KLOVS7ZEJHA4OAQ2FXX7JOHLXIP",
+    // Blend
+    "CDVQVKOY2YSXS2IC7KN6MNASSHPAO7UN2UR2ON4OI2SKMFJNVAMDX6DP",
+    "CBP7NO6F7FRDHSOFQBT2L2UWYIZ2PU76JKVRYAQTG3KZSQLYAOKIF2WB",
+];
+
+fn is_tracked(address: &str) -> bool {
+    TRACKED_PROTOCOLS.contains(&address)
+}
+
+/// Kind of a SAC balance change, relative to the tracked protocol.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Operation {
+    Deposit,
+    Withdraw,
+    Mint,
+    Burn,
+    Clawback,
+}
+
+impl Operation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Operation::Deposit => "deposit",
+            Operation::Withdraw => "withdraw",
+            Operation::Mint => "mint",
+            Operation::Burn => "burn",
+            Operation::Clawback => "clawback",
+        }
+    }
+
+    fn tvl_delta(self, amount: i128) -> i128 {
+        match self {
+            Operation::Deposit | Operation::Mint => amount,
+            Operation::Withdraw | Operation::Burn | Operation::Clawback => -amount,
+        }
+    }
+}
+
+/// Amount carried by a SAC event. The data is either the plain `i128`
+/// amount or, for muxed transfers, a map with an `amount` entry.
+fn event_amount(data: &ScVal) -> Option<i128> {
+    match data {
+        ScVal::I128(Int128Parts { hi, lo }) => Some(((*hi as i128) << 64) | *lo as i128),
+        ScVal::Map(Some(map)) => map
+            .0
+            .iter()
+            .find(|entry| symbol(&entry.key).as_deref() == Some("amount"))
+            .and_then(|entry| event_amount(&entry.val)),
+        _ => None,
+    }
+}
+
+fn symbol(val: &ScVal) -> Option<String> {
+    let ScVal::Symbol(ScSymbol(symbol)) = val else {
+        return None;
+    };
+
+    Some(symbol.to_utf8_string_lossy())
+}
+
+fn address_strkey(val: &ScVal) -> Option<String> {
+    match val {
+        ScVal::Address(ScAddress::Contract(Hash(hash))) => {
+            Some(stellar_strkey::Contract(*hash).to_string())
+        }
+        ScVal::Address(ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(
+            Uint256(key),
+        )))) => Some(stellar_strkey::ed25519::PublicKey(*key).to_string()),
+        _ => None,
+    }
+}
+
+/// Balance changes of a SAC event as (address, operation) pairs.
+///
+/// Addresses are read from the topics, skipping the trailing asset name.
+/// `mint` and `clawback` may or may not carry the admin before the affected
+/// address depending on the protocol version, so the last address is used.
+fn sac_flows(topics: &[ScVal]) -> Vec<(String, Operation)> {
+    let Some(action) = topics.first().and_then(symbol) else {
+        return vec![];
+    };
+    let addresses: Vec<String> = topics[1..].iter().filter_map(address_strkey).collect();
+
+    let flows = match action.as_str() {
+        "transfer" if addresses.len() >= 2 => vec![
+            (addresses[0].clone(), Operation::Withdraw),
+            (addresses[1].clone(), Operation::Deposit),
+        ],
+        "mint" => addresses
+            .last()
+            .map(|to| (to.clone(), Operation::Mint))
+            .into_iter()
+            .collect(),
+        "burn" => addresses
+            .first()
+            .map(|from| (from.clone(), Operation::Burn))
+            .into_iter()
+            .collect(),
+        "clawback" => addresses
+            .last()
+            .map(|from| (from.clone(), Operation::Clawback))
+            .into_iter()
+            .collect(),
+        _ => vec![],
+    };
+
+    flows
+        .into_iter()
+        .filter(|(address, _)| is_tracked(address))
+        .collect()
+}
+
+fn record_operation(
+    env: &EnvClient,
+    states: &mut StateCache,
+    sac: &str,
+    target: String,
+    amount: i128,
+    operation: Operation,
+    hash: &[u8; 32],
+) {
+    let state = states.get_mut(env, sac, &target);
+    state.t_count += 1;
+    state.t_volume += amount;
+    state.t_tvl += operation.tvl_delta(amount);
+
+    env.put(&SACDefiOperation {
+        sac: sac.to_string(),
+        t_count: state.t_count,
+        t_tvl: state.t_tvl,
+        t_volume: state.t_volume,
+        target,
+        amount,
+        operation: operation.as_str().into(),
+        tx: hex::encode(hash),
+        ledger: env.reader().ledger_sequence(),
+        timestamp: env.reader().ledger_timestamp(),
+        id: state.t_count,
+    });
 }
 
 #[no_mangle]
 pub extern "C" fn on_close() {
     let env = EnvClient::new();
 
-    env.log().debug(format!("Starting processing"), None);
+    env.log().debug("Starting processing", None);
 
+    let mut states = StateCache::new();
     for (event, hash) in env.reader().pretty().soroban_events_and_txhash() {
-        let contract = event.contract;
-
-        let topics = event.topics.to_vec();
-        if let Ok(topic1) = env.try_from_scval::<Symbol>(&topics[0]) {
-            if topic1 == Symbol::new(&env.soroban(), "transfer") {
-                let sac_string = stellar_strkey::Contract(contract).to_string();
-                env.log().debug(format!("got transfer"), None);
-
-                let from = env.try_from_scval::<Address>(&topics[1]);
-                let to = env.try_from_scval::<Address>(&topics[2]);
-                let amount = env.try_from_scval(&event.data);
-
-                if let (Ok(from), Ok(to), Ok(amount)) = (from, to, amount) {
-                    let from = address_to_alloc_string(&env, from);
-                    let to = address_to_alloc_string(&env, to);
-
-                    let (from_current_id, from_count, from_tvl, from_volume) =
-                        get_current_state_for_target(&env, sac_string.clone(), from.clone());
-
-                    let from_as_target = SACDefiOperation {
-                        sac: sac_string.clone(),
-                        t_count: from_count + 1,
-                        t_tvl: from_tvl - amount,
-                        t_volume: from_volume + amount,
-                        target: from,
-                        amount,
-                        operation: "transfer".into(),
-                        tx: hex::encode(hash),
-                        ledger: env.reader().ledger_sequence(),
-                        timestamp: env.reader().ledger_timestamp(),
-                        id: from_current_id + 1,
-                    };
-
-                    let (to_current_id, to_count, to_tvl, to_volume) =
-                        get_current_state_for_target(&env, sac_string.clone(), to.clone());
-
-                    let to_as_target = SACDefiOperation {
-                        sac: sac_string,
-                        t_count: to_count + 1,
-                        t_tvl: to_tvl + amount,
-                        t_volume: to_volume + amount,
-                        target: to,
-                        amount,
-                        operation: "transfer".into(),
-                        tx: hex::encode(hash),
-                        ledger: env.reader().ledger_sequence(),
-                        timestamp: env.reader().ledger_timestamp(),
-                        id: to_current_id + 1,
-                    };
-
-                    env.put(&from_as_target);
-                    env.put(&to_as_target);
-
-                    env.log().debug(format!("execution succeded"), None);
-                }
+        let flows = sac_flows(&event.topics.to_vec());
+        if flows.is_empty() {
+            continue;
+        }
+
+        let Some(amount) = event_amount(&event.data) else {
+            env.log()
+                .debug("skipping event with unknown data shape", None);
+            continue;
+        };
+        let sac_string = stellar_strkey::Contract(event.contract).to_string();
+
+        for (target, operation) in flows {
+            env.log()
+                .debug(format!("got {} for {}", operation.as_str(), target), None);
+            record_operation(
+                &env,
+                &mut states,
+                &sac_string,
+                target,
+                amount,
+                operation,
+                &hash,
+            );
+        }
+    }
+
+    states.flush(&env);
+}
+
+/// Backfills `defistate` from the latest `sacdefiop` row of every target
+/// that has none yet. To be called once after deploying `defistate`, until
+/// then the ranking only covers targets touched since.
+#[no_mangle]
+pub extern "C" fn seed_state() {
+    let env = EnvClient::empty();
+    let existing: HashSet<(String, String)> = env
+        .read::<SACDefiState>()
+        .into_iter()
+        .map(|state| (state.sac, state.target))
+        .collect();
+
+    let mut latest: HashMap<(String, String), SACDefiTotals> = HashMap::new();
+    for row in env.read::<SACDefiTotals>() {
+        let key = (row.sac.clone(), row.target.clone());
+        if existing.contains(&key) {
+            continue;
+        }
+
+        match latest.get(&key) {
+            Some(current) if current.id >= row.id => {}
+            _ => {
+                latest.insert(key, row);
             }
         }
     }
+
+    let seeded = latest.len();
+    for (_, totals) in latest {
+        env.put(&SACDefiState::from(totals));
+    }
+
+    env.conclude(format!("seeded {} targets", seeded))
 }
 
 #[derive(DatabaseDerive, Clone)]
@@ -183,35 +375,211 @@ pub struct SACDefiOperationMinify {
 #[no_mangle]
 pub extern "C" fn dashboard() {
     let env = EnvClient::empty();
-    let dasboard = {
-        env.log().debug("building chart", None);
-        let dashboard = build_sac_dashboard(&env);
-        env.log().debug("chart built", None);
-        dashboard
+    env.log().debug("building chart", None);
+
+    match build_sac_dashboard(&env) {
+        Ok(dashboard) => {
+            env.log().debug("chart built", None);
+            env.conclude(&dashboard)
+        }
+        Err(error) => {
+            env.log().error(&error, None);
+            env.conclude(&DashboardError { error })
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DashboardError {
+    error: String,
+}
+
+/// Number of targets ranked when the request doesn't specify it.
+const DEFAULT_TOP_TARGETS: usize = 5;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum RankBy {
+    #[default]
+    Tvl,
+    Volume,
+}
+
+const NETWORK_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+
+#[derive(Clone)]
+#[contracttype]
+pub struct StellarAssetContractMetadata {
+    pub decimal: u32,
+    pub name: SorobanString,
+    pub symbol: SorobanString,
+}
+
+/// Contract id of the SAC for a classic asset, either `native` or
+/// `CODE:ISSUER`.
+fn sac_contract_id(asset: &str) -> Option<[u8; 32]> {
+    let asset = if asset == "native" {
+        Asset::Native
+    } else {
+        let (code, issuer) = asset.split_once(':')?;
+        let issuer = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            stellar_strkey::ed25519::PublicKey::from_string(issuer)
+                .ok()?
+                .0,
+        )));
+
+        match code.len() {
+            1..=4 => {
+                let mut asset_code = [0; 4];
+                asset_code[..code.len()].copy_from_slice(code.as_bytes());
+                Asset::CreditAlphanum4(AlphaNum4 {
+                    asset_code: AssetCode4(asset_code),
+                    issuer,
+                })
+            }
+            5..=12 => {
+                let mut asset_code = [0; 12];
+                asset_code[..code.len()].copy_from_slice(code.as_bytes());
+                Asset::CreditAlphanum12(AlphaNum12 {
+                    asset_code: AssetCode12(asset_code),
+                    issuer,
+                })
+            }
+            _ => return None,
+        }
     };
 
-    env.conclude(&dasboard)
+    let preimage = HashIdPreimage::ContractId(HashIdPreimageContractId {
+        network_id: Hash(sha256(NETWORK_PASSPHRASE.as_bytes())),
+        contract_id_preimage: ContractIdPreimage::Asset(asset),
+    });
+
+    Some(sha256(&preimage.to_xdr(Limits::none()).ok()?))
+}
+
+/// Resolves the SAC's strkey from the request. `searched_sac` takes
+/// precedence, as callers predating `asset` resolution pass the display name
+/// as `asset` and the contract there.
+fn resolve_sac(asset: &str, searched_sac: Option<&str>) -> Result<String, String> {
+    if let Some(sac) = searched_sac {
+        return match stellar_strkey::Contract::from_string(sac) {
+            Ok(_) => Ok(sac.to_string()),
+            Err(_) => Err(format!("searched_sac {} is not a contract strkey", sac)),
+        };
+    }
+
+    if stellar_strkey::Contract::from_string(asset).is_ok() {
+        return Ok(asset.to_string());
+    }
+
+    sac_contract_id(asset)
+        .map(|contract| stellar_strkey::Contract(contract).to_string())
+        .ok_or(format!(
+            "asset {} must be a contract strkey, `native` or `CODE:ISSUER`",
+            asset
+        ))
+}
+
+/// Symbol from the SAC's `METADATA` instance entry.
+fn sac_symbol(env: &EnvClient, sac: &str) -> Option<String> {
+    let contract = stellar_strkey::Contract::from_string(sac).ok()?.0;
+    let instance = env.read_contract_instance(contract).ok()??;
+    let LedgerEntryData::ContractData(data) = instance.entry.data else {
+        return None;
+    };
+    let ScVal::ContractInstance(instance) = data.val else {
+        return None;
+    };
+
+    let key = env.to_scval(Symbol::new(env.soroban(), "METADATA"));
+    let entry = instance
+        .storage?
+        .0
+        .iter()
+        .find(|entry| entry.key == key)?
+        .clone();
+    let meta: StellarAssetContractMetadata = env.try_from_scval(&entry.val).ok()?;
+
+    Some(soroban_string_to_alloc_string(env, meta.symbol))
 }
 
 #[derive(Deserialize)]
 pub struct Request {
+    /// SAC strkey, `native` or `CODE:ISSUER`.
     asset: String,
-    searched_sac: String,
-    top_targets: Vec<String>,
+    /// Deprecated, SAC strkey for callers passing a display name as `asset`.
+    searched_sac: Option<String>,
+    /// Targets to chart, overrides the automatic ranking.
+    top_targets: Option<Vec<String>>,
+    /// Metric the targets are ranked by, `tvl` (default) or `volume`.
+    #[serde(default)]
+    rank_by: RankBy,
+    /// Number of ranked targets.
+    limit: Option<usize>,
 }
 
-pub fn build_sac_dashboard(env: &EnvClient) -> Dashboard {
+/// Current states of all the targets of a SAC, best first.
+fn rank_targets(env: &EnvClient, sac: &str, rank_by: RankBy) -> Vec<SACDefiState> {
+    let mut states: Vec<SACDefiState> = env
+        .read_filter()
+        .column_equal_to("sac", sac.to_string())
+        .read()
+        .unwrap();
+
+    match rank_by {
+        RankBy::Tvl => states.sort_by_key(|state| std::cmp::Reverse(state.t_tvl)),
+        RankBy::Volume => states.sort_by_key(|state| std::cmp::Reverse(state.t_volume)),
+    }
+
+    states
+}
+
+pub fn build_sac_dashboard(env: &EnvClient) -> Result<Dashboard, String> {
     let Request {
         asset,
         searched_sac,
         top_targets,
+        rank_by,
+        limit,
     } = env.read_request_body();
+    let searched_sac = resolve_sac(&asset, searched_sac.as_deref())?;
+    let asset = sac_symbol(env, &searched_sac).unwrap_or(asset);
 
     let mut dashboard = DashboardBuilder::new(
         &format!("SAC DeFi Activity Dashboard for Asset {asset}",),
         "Explore the DeFi activity of Stellar Asset Contracts",
     );
 
+    let mut ranking = rank_targets(env, &searched_sac, rank_by);
+    ranking.truncate(limit.unwrap_or(DEFAULT_TOP_TARGETS));
+
+    dashboard = dashboard.add_table(
+        &format!("Top targets for {}", asset),
+        vec![
+            "rank".into(),
+            "target".into(),
+            "tvl".into(),
+            "volume".into(),
+            "operations".into(),
+        ],
+        ranking
+            .iter()
+            .enumerate()
+            .map(|(idx, state)| {
+                vec![
+                    (idx + 1).to_string(),
+                    state.target.clone(),
+                    ((state.t_tvl as f64) / 10_000_000.0).to_string(),
+                    ((state.t_volume as f64) / 10_000_000.0).to_string(),
+                    state.t_count.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    let top_targets =
+        top_targets.unwrap_or_else(|| ranking.into_iter().map(|state| state.target).collect());
+
     for target in top_targets {
         // Get TVL evolution for this target
         let mut tvl_data: Vec<(u32, i128)> = env
@@ -227,7 +595,7 @@ pub fn build_sac_dashboard(env: &EnvClient) -> Dashboard {
 
         // Add TVL evolution chart for this target
         dashboard = dashboard.add_line_chart(
-            &format!("TVL Evolution for {} - Target: {}", searched_sac, target),
+            &format!("TVL Evolution for {} - Target: {}", asset, target),
             tvl_data
                 .iter()
                 .map(|(ledger, _)| ledger.to_string())
@@ -255,13 +623,13 @@ pub fn build_sac_dashboard(env: &EnvClient) -> Dashboard {
 
         // Add volume evolution chart for this target
         dashboard = dashboard.add_line_chart(
-            &format!("Volume Evolution for {} - Target: {}", searched_sac, target),
+            &format!("Volume Evolution for {} - Target: {}", asset, target),
             volume_data
                 .iter()
                 .map(|(ledger, _)| ledger.to_string())
                 .collect(),
             vec![(
-                &format!("Volume of {} for {}", searched_sac, target),
+                &format!("Volume of {} for {}", asset, target),
                 volume_data
                     .iter()
                     .map(|(_, volume)| ((*volume as f64) / 10_000_000.0) as i64)
@@ -270,5 +638,127 @@ pub fn build_sac_dashboard(env: &EnvClient) -> Dashboard {
         );
     }
 
-    dashboard.build()
+    Ok(dashboard.build())
+}
+
+#[cfg(test)]
+fn tracked() -> ScVal {
+    let contract = stellar_strkey::Contract::from_string(TRACKED_PROTOCOLS[3]).unwrap();
+    ScVal::Address(ScAddress::Contract(Hash(contract.0)))
+}
+
+#[cfg(test)]
+fn account(byte: u8) -> ScVal {
+    ScVal::Address(ScAddress::Account(AccountId(
+        PublicKey::PublicKeyTypeEd25519(Uint256([byte; 32])),
+    )))
+}
+
+#[cfg(test)]
+fn topics(action: &str, addresses: &[ScVal]) -> Vec<ScVal> {
+    use zephyr_sdk::soroban_sdk::xdr::ScString;
+
+    let mut topics = vec![ScVal::Symbol(ScSymbol(action.try_into().unwrap()))];
+    topics.extend(addresses.iter().cloned());
+    // trailing asset name, as emitted by the SAC.
+    topics.push(ScVal::String(ScString("USDC:GA5Z".try_into().unwrap())));
+
+    topics
+}
+
+#[test]
+fn transfer_flows() {
+    let protocol = TRACKED_PROTOCOLS[3].to_string();
+
+    let deposit = topics("transfer", &[account(1), tracked()]);
+    assert_eq!(
+        sac_flows(&deposit),
+        vec![(protocol.clone(), Operation::Deposit)]
+    );
+
+    let withdraw = topics("transfer", &[tracked(), account(1)]);
+    assert_eq!(sac_flows(&withdraw), vec![(protocol, Operation::Withdraw)]);
+
+    let untracked = topics("transfer", &[account(1), account(2)]);
+    assert!(sac_flows(&untracked).is_empty());
+}
+
+#[test]
+fn mint_burn_clawback_flows() {
+    let protocol = TRACKED_PROTOCOLS[3].to_string();
+
+    // (mint, admin, to) before protocol 23, (mint, to) after.
+    let old_mint = topics("mint", &[account(1), tracked()]);
+    assert_eq!(
+        sac_flows(&old_mint),
+        vec![(protocol.clone(), Operation::Mint)]
+    );
+    let new_mint = topics("mint", &[tracked()]);
+    assert_eq!(
+        sac_flows(&new_mint),
+        vec![(protocol.clone(), Operation::Mint)]
+    );
+    let admin_only = topics("mint", &[tracked(), account(1)]);
+    assert!(sac_flows(&admin_only).is_empty());
+
+    let burn = topics("burn", &[tracked()]);
+    assert_eq!(sac_flows(&burn), vec![(protocol.clone(), Operation::Burn)]);
+
+    // (clawback, admin, from) before protocol 23, (clawback, from) after.
+    let old_clawback = topics("clawback", &[account(1), tracked()]);
+    assert_eq!(
+        sac_flows(&old_clawback),
+        vec![(protocol.clone(), Operation::Clawback)]
+    );
+    let new_clawback = topics("clawback", &[tracked()]);
+    assert_eq!(
+        sac_flows(&new_clawback),
+        vec![(protocol, Operation::Clawback)]
+    );
+
+    assert!(sac_flows(&topics("approve", &[tracked(), account(1)])).is_empty());
+}
+
+#[test]
+fn event_amounts() {
+    use zephyr_sdk::soroban_sdk::xdr::{ScMap, ScMapEntry};
+
+    let amount = ScVal::I128(Int128Parts { hi: 0, lo: 1_000 });
+    assert_eq!(event_amount(&amount), Some(1_000));
+
+    let negative = ScVal::I128(Int128Parts {
+        hi: -1,
+        lo: u64::MAX,
+    });
+    assert_eq!(event_amount(&negative), Some(-1));
+
+    // muxed transfers carry `{amount, to_muxed_id}`.
+    let muxed = ScVal::Map(Some(ScMap(
+        vec![
+            ScMapEntry {
+                key: ScVal::Symbol(ScSymbol("amount".try_into().unwrap())),
+                val: amount.clone(),
+            },
+            ScMapEntry {
+                key: ScVal::Symbol(ScSymbol("to_muxed_id".try_into().unwrap())),
+                val: ScVal::U64(7),
+            },
+        ]
+        .try_into()
+        .unwrap(),
+    )));
+    assert_eq!(event_amount(&muxed), Some(1_000));
+
+    assert_eq!(event_amount(&ScVal::U64(7)), None);
+}
+
+#[test]
+fn resolves_sacs() {
+    let native = "CAS3J7GYLGXMF6TDJBBYYSE3HQ6BBSMLNUQ34T6TZMYMW2EVH34XOWMA";
+    assert_eq!(resolve_sac("native", None).unwrap(), native);
+    assert_eq!(resolve_sac(native, None).unwrap(), native);
+    assert_eq!(resolve_sac("XLM", Some(native)).unwrap(), native);
+
+    assert!(resolve_sac("USDC", None).is_err());
+    assert!(resolve_sac("XLM", Some("native")).is_err());
 }
@@ -0,0 +1,279 @@
+use serde::Serialize;
+use zephyr_sdk::{
+    prelude::*,
+    soroban_sdk::xdr::{Hash, Int128Parts, PublicKey, ScAddress, ScVal},
+    DatabaseDerive, EnvClient,
+};
+
+use crate::factories;
+
+#[cfg(test)]
+use zephyr_sdk::soroban_sdk::xdr::{AccountId, ScMap, ScMapEntry, ScString, ScSymbol, ScVec, Uint256};
+
+const LENDING_ACTIONS: [&str; 6] = [
+    "supply",
+    "withdraw",
+    "supply_collateral",
+    "withdraw_collateral",
+    "borrow",
+    "repay",
+];
+
+// Decoded event involving a wallet, newest first when queried.
+#[derive(DatabaseDerive, Clone, Serialize)]
+#[with_name("activity")]
+pub struct Activity {
+    /// Sequence of the event, see `event_seq`.
+    pub id: u64,
+    pub address: String,
+    /// `transfer_in`, `transfer_out`, `swap`, `lending`, `signer` or `unknown`.
+    pub kind: String,
+    /// Token moved, or sold for swaps, empty when not known.
+    pub token: String,
+    /// Counterparty of the wallet, empty when not known.
+    pub cparty: String,
+    pub amount: i128,
+    pub tx: String,
+    pub ledger: u32,
+}
+
+/// What an event means for the wallet it involves.
+pub struct Classified {
+    pub kind: &'static str,
+    pub token: String,
+    pub cparty: String,
+    pub amount: i128,
+}
+
+impl Classified {
+    fn new(kind: &'static str, token: String, cparty: String, amount: i128) -> Self {
+        Self {
+            kind,
+            token,
+            cparty,
+            amount,
+        }
+    }
+}
+
+/// Classifies an event emitted by `contract` that involves `wallet`.
+pub fn classify(wallet: [u8; 32], contract: [u8; 32], topics: &[ScVal], data: &ScVal) -> Classified {
+    let name = topics.first().and_then(symbol);
+    let contract_strkey = stellar_strkey::Contract(contract).to_string();
+
+    match name.as_deref() {
+        // token events: transfer(from, to) and burn(from). mint and clawback
+        // carry the admin before the affected address up to protocol 22 only,
+        // so the affected address is the last one.
+        Some("transfer") | Some("mint") | Some("burn") | Some("clawback") => {
+            let addresses: Vec<&ScVal> = topics[1..].iter().filter(|topic| address_bytes(topic).is_some()).collect();
+            let amount = event_amount(data).unwrap_or(0);
+
+            let (kind, counterparty) = match (name.as_deref(), addresses.as_slice()) {
+                (Some("transfer"), [from, to, ..]) if address_bytes(from) == Some(wallet) => ("transfer_out", Some(*to)),
+                (Some("transfer"), [from, ..]) => ("transfer_in", Some(*from)),
+                (Some("burn"), _) => ("transfer_out", None),
+                // the wallet is the admin minting to, or clawing back from, the last address.
+                (Some("mint"), [.., affected]) if address_bytes(affected) != Some(wallet) => ("transfer_out", Some(*affected)),
+                (Some("clawback"), [.., affected]) if address_bytes(affected) != Some(wallet) => ("transfer_in", Some(*affected)),
+                (Some("mint"), [admin, _]) => ("transfer_in", Some(*admin)),
+                (Some("mint"), _) => ("transfer_in", None),
+                (_, [admin, _]) => ("transfer_out", Some(*admin)),
+                _ => ("transfer_out", None),
+            };
+
+            Classified::new(kind, contract_strkey, counterparty.and_then(address_strkey).unwrap_or_default(), amount)
+        }
+
+        // Blend pool events: (action, asset, user) with [amount, b/d tokens] as data.
+        Some(action) if LENDING_ACTIONS.contains(&action) => {
+            let asset = topics.get(1).and_then(address_strkey).unwrap_or_default();
+            let amount = match data {
+                ScVal::Vec(Some(values)) => values.0.first().and_then(event_amount).unwrap_or(0),
+                other => event_amount(other).unwrap_or(0),
+            };
+
+            Classified::new("lending", asset, contract_strkey, amount)
+        }
+
+        _ if topics.iter().filter_map(symbol).any(|name| name == "swap") => {
+            let (token, amount) = swap_input(data);
+            Classified::new("swap", token, contract_strkey, amount)
+        }
+
+        // signer events are emitted by the wallet with the factory as first topic.
+        _ if contract == wallet && is_signer_event(topics) => {
+            Classified::new("signer", String::new(), String::new(), 0)
+        }
+
+        _ => Classified::new("unknown", String::new(), contract_strkey, 0),
+    }
+}
+
+fn is_signer_event(topics: &[ScVal]) -> bool {
+    let factory = topics.first().and_then(address_bytes).and_then(factories::by_id);
+    let (Some(factory), Some(name)) = (factory, topics.get(1).and_then(symbol)) else {
+        return false;
+    };
+
+    name == factory.schema().add_signer || name == factory.schema().remove_signer
+}
+
+fn symbol(val: &ScVal) -> Option<String> {
+    let ScVal::Symbol(symbol) = val else {
+        return None;
+    };
+
+    Some(symbol.0.to_utf8_string_lossy())
+}
+
+fn address_bytes(val: &ScVal) -> Option<[u8; 32]> {
+    match val {
+        ScVal::Address(ScAddress::Contract(Hash(hash))) => Some(*hash),
+        ScVal::Address(ScAddress::Account(account)) => {
+            let PublicKey::PublicKeyTypeEd25519(pubkey) = &account.0;
+            Some(pubkey.0)
+        }
+        _ => None,
+    }
+}
+
+fn address_strkey(val: &ScVal) -> Option<String> {
+    match val {
+        ScVal::Address(ScAddress::Contract(Hash(hash))) => Some(stellar_strkey::Contract(*hash).to_string()),
+        ScVal::Address(ScAddress::Account(account)) => {
+            let PublicKey::PublicKeyTypeEd25519(pubkey) = &account.0;
+            Some(stellar_strkey::ed25519::PublicKey(pubkey.0).to_string())
+        }
+        _ => None,
+    }
+}
+
+fn field<'a>(val: &'a ScVal, name: &str) -> Option<&'a ScVal> {
+    let ScVal::Map(Some(map)) = val else {
+        return None;
+    };
+
+    map.0.iter().find(|entry| symbol(&entry.key).as_deref() == Some(name)).map(|entry| &entry.val)
+}
+
+/// The `i128` amount of an event, either bare or as an `amount` map entry.
+fn event_amount(val: &ScVal) -> Option<i128> {
+    match val {
+        ScVal::I128(Int128Parts { hi, lo }) => Some(((*hi as i128) << 64) | *lo as i128),
+        ScVal::Map(Some(_)) => field(val, "amount").and_then(event_amount),
+        _ => None,
+    }
+}
+
+/// Token and amount sold in a Soroswap swap. Router events carry the whole
+/// `path` and `amounts`, pair events only the amounts of their two tokens, so
+/// the token is left empty for them.
+fn swap_input(data: &ScVal) -> (String, i128) {
+    if let (Some(ScVal::Vec(Some(path))), Some(ScVal::Vec(Some(amounts)))) = (field(data, "path"), field(data, "amounts")) {
+        let token = path.0.first().and_then(address_strkey).unwrap_or_default();
+        let amount = amounts.0.first().and_then(event_amount).unwrap_or(0);
+        return (token, amount);
+    }
+
+    let amount = ["amount_0_in", "amount_1_in"]
+        .iter()
+        .filter_map(|name| field(data, name).and_then(event_amount))
+        .find(|amount| *amount != 0)
+        .unwrap_or(0);
+
+    (String::new(), amount)
+}
+
+#[cfg(test)]
+const WALLET: [u8; 32] = [1; 32];
+#[cfg(test)]
+const TOKEN: [u8; 32] = [2; 32];
+
+#[cfg(test)]
+fn sym(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+#[cfg(test)]
+fn contract(id: [u8; 32]) -> ScVal {
+    ScVal::Address(ScAddress::Contract(Hash(id)))
+}
+
+#[cfg(test)]
+fn account(byte: u8) -> ScVal {
+    ScVal::Address(ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([byte; 32])))))
+}
+
+#[cfg(test)]
+fn i128_val(amount: i128) -> ScVal {
+    ScVal::I128(Int128Parts { hi: (amount >> 64) as i64, lo: amount as u64 })
+}
+
+#[cfg(test)]
+fn scvec(values: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(values.try_into().unwrap())))
+}
+
+#[cfg(test)]
+fn scmap(fields: Vec<(&str, ScVal)>) -> ScVal {
+    let entries: Vec<ScMapEntry> = fields.into_iter().map(|(key, val)| ScMapEntry { key: sym(key), val }).collect();
+    ScVal::Map(Some(ScMap(entries.try_into().unwrap())))
+}
+
+/// SAC event topics, with the trailing asset name.
+#[cfg(test)]
+fn sac(action: &str, addresses: Vec<ScVal>) -> Vec<ScVal> {
+    let mut topics = vec![sym(action)];
+    topics.extend(addresses);
+    topics.push(ScVal::String(ScString("USDC:GA5Z".try_into().unwrap())));
+    topics
+}
+
+#[cfg(test)]
+fn strkey(val: &ScVal) -> String {
+    address_strkey(val).unwrap()
+}
+
+#[test]
+fn classifies() {
+    let wallet = contract(WALLET);
+    let token = stellar_strkey::Contract(TOKEN).to_string();
+    let factory = contract(factories::FACTORIES[0].id);
+    let router = scmap(vec![
+        ("amounts", scvec(vec![i128_val(10), i128_val(20)])),
+        ("path", scvec(vec![contract([4; 32]), contract([5; 32])])),
+        ("to", wallet.clone()),
+    ]);
+    let pair = scmap(vec![
+        ("amount_0_in", i128_val(0)),
+        ("amount_0_out", i128_val(20)),
+        ("amount_1_in", i128_val(10)),
+        ("amount_1_out", i128_val(0)),
+        ("to", wallet.clone()),
+    ]);
+
+    // (case, emitter, topics, data, kind, token, counterparty, amount)
+    let cases = [
+        ("transfer out", TOKEN, sac("transfer", vec![wallet.clone(), account(3)]), i128_val(5), "transfer_out", token.clone(), strkey(&account(3)), 5),
+        ("transfer in", TOKEN, sac("transfer", vec![account(3), wallet.clone()]), i128_val(5), "transfer_in", token.clone(), strkey(&account(3)), 5),
+        ("mint with admin", TOKEN, sac("mint", vec![account(3), wallet.clone()]), i128_val(5), "transfer_in", token.clone(), strkey(&account(3)), 5),
+        ("mint", TOKEN, sac("mint", vec![wallet.clone()]), i128_val(5), "transfer_in", token.clone(), String::new(), 5),
+        ("mint by the wallet", TOKEN, sac("mint", vec![wallet.clone(), account(3)]), i128_val(5), "transfer_out", token.clone(), strkey(&account(3)), 5),
+        ("clawback with admin", TOKEN, sac("clawback", vec![account(3), wallet.clone()]), i128_val(5), "transfer_out", token.clone(), strkey(&account(3)), 5),
+        ("clawback", TOKEN, sac("clawback", vec![wallet.clone()]), i128_val(5), "transfer_out", token.clone(), String::new(), 5),
+        ("burn", TOKEN, sac("burn", vec![wallet.clone()]), scmap(vec![("amount", i128_val(5))]), "transfer_out", token.clone(), String::new(), 5),
+        ("lending", [6; 32], vec![sym("supply"), contract(TOKEN), wallet.clone()], scvec(vec![i128_val(7), i128_val(6)]), "lending", token.clone(), stellar_strkey::Contract([6; 32]).to_string(), 7),
+        ("router swap", [6; 32], vec![sym("SoroswapRouter"), sym("swap")], router, "swap", stellar_strkey::Contract([4; 32]).to_string(), stellar_strkey::Contract([6; 32]).to_string(), 10),
+        ("pair swap", [6; 32], vec![sym("SoroswapPair"), sym("swap")], pair, "swap", String::new(), stellar_strkey::Contract([6; 32]).to_string(), 10),
+        ("signer", WALLET, vec![factory, sym(factories::FACTORIES[0].schema().add_signer), ScVal::Void], ScVal::Void, "signer", String::new(), String::new(), 0),
+    ];
+
+    for (case, emitter, topics, data, kind, token, counterparty, amount) in cases {
+        let classified = classify(WALLET, emitter, &topics, &data);
+        assert_eq!(classified.kind, kind, "{}", case);
+        assert_eq!(classified.token, token, "{}", case);
+        assert_eq!(classified.cparty, counterparty, "{}", case);
+        assert_eq!(classified.amount, amount, "{}", case);
+    }
+}
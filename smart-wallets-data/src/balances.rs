@@ -0,0 +1,166 @@
+use serde::Serialize;
+use zephyr_sdk::{
+    soroban_sdk::xdr::{Hash, Int128Parts, LedgerEntryData, ScAddress, ScSymbol, ScVal, ScVec},
+    EnvClient,
+};
+
+/// Decimals assumed when a token doesn't expose SAC metadata.
+pub const DEFAULT_DECIMALS: u32 = 7;
+
+#[derive(Serialize)]
+pub struct Balance {
+    token: String,
+    symbol: String,
+    decimals: u32,
+    /// Raw amount, as a string since it may not fit a JSON number.
+    balance: String,
+    formatted: String,
+}
+
+/// Balance of a requested token, or why it couldn't be read.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum TokenBalance {
+    Balance(Balance),
+    Error { token: String, error: String },
+}
+
+/// Parses the wallet whose balances are read. Only smart wallets are
+/// accepted, as the balances of `G` accounts aren't stored as contract data.
+pub fn holder(address: &str) -> Result<[u8; 32], String> {
+    match stellar_strkey::Strkey::from_string(address) {
+        Ok(stellar_strkey::Strkey::Contract(contract)) => Ok(contract.0),
+        Ok(_) => Err(format!("{} is not a contract, balances are only read for smart wallets", address)),
+        Err(_) => Err(format!("invalid address {}", address)),
+    }
+}
+
+/// Balance of `holder` in `token`, zero when there's no balance entry.
+pub fn balance(env: &EnvClient, holder: [u8; 32], token: &str) -> TokenBalance {
+    match read_balance(env, holder, token) {
+        Ok(balance) => TokenBalance::Balance(balance),
+        Err(error) => TokenBalance::Error { token: token.to_string(), error: error.into() },
+    }
+}
+
+fn read_balance(env: &EnvClient, holder: [u8; 32], token: &str) -> Result<Balance, &'static str> {
+    let token_id = stellar_strkey::Contract::from_string(token).map_err(|_| "invalid token contract")?.0;
+
+    let amount = match env.read_contract_entry_by_scvalkey(token_id, balance_key(holder)) {
+        Ok(Some(entry)) => {
+            let LedgerEntryData::ContractData(data) = entry.entry.data else {
+                return Err("balance entry is not contract data");
+            };
+            amount(&data.val).ok_or("unknown balance layout")?
+        }
+        Ok(None) => 0,
+        Err(_) => return Err("couldn't read the balance entry"),
+    };
+    let (symbol, decimals) = metadata(env, token_id).unwrap_or_else(|| (token.to_string(), DEFAULT_DECIMALS));
+
+    Ok(Balance {
+        token: token.to_string(),
+        symbol,
+        decimals,
+        balance: amount.to_string(),
+        formatted: format_amount(amount, decimals),
+    })
+}
+
+fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+/// `Balance(holder)` storage key, shared by the SAC and the token SDK.
+fn balance_key(holder: [u8; 32]) -> ScVal {
+    let key = vec![symbol("Balance"), ScVal::Address(ScAddress::Contract(Hash(holder)))];
+    ScVal::Vec(Some(ScVec(key.try_into().unwrap())))
+}
+
+fn field<'a>(val: &'a ScVal, name: &str) -> Option<&'a ScVal> {
+    let ScVal::Map(Some(map)) = val else {
+        return None;
+    };
+
+    map.0.iter().find(|entry| entry.key == symbol(name)).map(|entry| &entry.val)
+}
+
+/// Token SDK based tokens store a bare `i128`, Stellar Asset Contracts an
+/// `{ amount, authorized, clawback }` map.
+fn amount(val: &ScVal) -> Option<i128> {
+    match val {
+        ScVal::I128(Int128Parts { hi, lo }) => Some(((*hi as i128) << 64) | *lo as i128),
+        ScVal::Map(Some(_)) => field(val, "amount").and_then(amount),
+        _ => None,
+    }
+}
+
+/// Symbol and decimals from the SAC (and token SDK) `METADATA` instance entry.
+fn metadata(env: &EnvClient, token: [u8; 32]) -> Option<(String, u32)> {
+    let instance = env.read_contract_instance(token).ok()??;
+    let LedgerEntryData::ContractData(data) = instance.entry.data else {
+        return None;
+    };
+    let ScVal::ContractInstance(instance) = data.val else {
+        return None;
+    };
+
+    let key = symbol("METADATA");
+    let storage = instance.storage?;
+    let meta = &storage.0.iter().find(|entry| entry.key == key)?.val;
+
+    match (field(meta, "symbol"), field(meta, "decimal")) {
+        (Some(ScVal::String(symbol)), Some(ScVal::U32(decimals))) => Some((symbol.0.to_utf8_string_lossy(), *decimals)),
+        _ => None,
+    }
+}
+
+/// Exact decimal representation of a raw token amount.
+pub fn format_amount(amount: i128, decimals: u32) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    let digits = amount.unsigned_abs().to_string();
+    let decimals = decimals as usize;
+
+    if decimals == 0 {
+        return format!("{}{}", sign, digits);
+    }
+
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+#[test]
+fn formats_amounts() {
+    assert_eq!(format_amount(0, 7), "0");
+    assert_eq!(format_amount(10_000_000, 7), "1");
+    assert_eq!(format_amount(12_345_000, 7), "1.2345");
+    assert_eq!(format_amount(5, 7), "0.0000005");
+    assert_eq!(format_amount(-15_000_000, 7), "-1.5");
+    assert_eq!(format_amount(42, 0), "42");
+    assert_eq!(format_amount(i128::MIN, 2), "-1701411834604692317316873037158841057.28");
+}
+
+#[test]
+fn decodes_amounts() {
+    use zephyr_sdk::soroban_sdk::xdr::{ScMap, ScMapEntry};
+
+    let raw = ScVal::I128(Int128Parts { hi: 1, lo: 5 });
+    assert_eq!(amount(&raw), Some((1 << 64) + 5));
+
+    let entries = vec![
+        ScMapEntry { key: symbol("amount"), val: ScVal::I128(Int128Parts { hi: 0, lo: 7 }) },
+        ScMapEntry { key: symbol("authorized"), val: ScVal::Bool(true) },
+        ScMapEntry { key: symbol("clawback"), val: ScVal::Bool(false) },
+    ];
+    let sac = ScVal::Map(Some(ScMap(entries.try_into().unwrap())));
+    assert_eq!(amount(&sac), Some(7));
+
+    assert_eq!(amount(&ScVal::U32(7)), None);
+}
@@ -0,0 +1,92 @@
+use zephyr_sdk::{
+    soroban_sdk::xdr::{LedgerEntryData, ScVal},
+    EnvClient,
+};
+
+/// Names and layout of the events a smart-wallet implementation emits.
+pub struct EventSchema {
+    /// Emitted by the factory, with the wallet as first address of the
+    /// topics and the initial signer id as data.
+    pub deploy: &'static str,
+    /// Emitted by the wallet as `(factory, add_signer, id)` with the key as data.
+    pub add_signer: &'static str,
+    /// Emitted by the wallet as `(factory, remove_signer, id)`.
+    pub remove_signer: &'static str,
+}
+
+pub const DEFAULT_SCHEMA: EventSchema = EventSchema {
+    deploy: "deploy",
+    add_signer: "add_sig",
+    remove_signer: "rm_sig",
+};
+
+pub struct Factory {
+    /// Raw contract id, kept decoded as it's compared against every event.
+    /// See `address` for the strkey.
+    pub id: [u8; 32],
+    /// Hex wasm hash of the wallets it deploys, contracts created with it are
+    /// tracked even if they never emit a signer event. When not set it's read
+    /// from the factory's instance storage, see `wallet_wasm`.
+    pub wasm_hash: Option<&'static str>,
+    /// `None` for implementations following `DEFAULT_SCHEMA`.
+    pub schema: Option<EventSchema>,
+}
+
+impl Factory {
+    pub fn address(&self) -> String {
+        stellar_strkey::Contract(self.id).to_string()
+    }
+
+    pub fn schema(&self) -> &EventSchema {
+        self.schema.as_ref().unwrap_or(&DEFAULT_SCHEMA)
+    }
+
+    /// Wasm hash of the wallets it deploys: `wasm_hash`, or else the only
+    /// 32-byte value of the factory's instance storage, where factories keep
+    /// the hash they deploy. `None` when there isn't exactly one.
+    pub fn wallet_wasm(&self, env: &EnvClient) -> Option<[u8; 32]> {
+        if let Some(hash) = self.wasm_hash {
+            return hex::decode(hash).ok()?.try_into().ok();
+        }
+
+        let instance = env.read_contract_instance(self.id).ok()??;
+        let LedgerEntryData::ContractData(data) = instance.entry.data else {
+            return None;
+        };
+        let ScVal::ContractInstance(instance) = data.val else {
+            return None;
+        };
+
+        let storage = instance.storage?;
+        let mut hashes = storage.0.iter().filter_map(|entry| match &entry.val {
+            ScVal::Bytes(bytes) => <[u8; 32]>::try_from(bytes.0.as_slice()).ok(),
+            _ => None,
+        });
+        match (hashes.next(), hashes.next()) {
+            (Some(hash), None) => Some(hash),
+            _ => None,
+        }
+    }
+}
+
+/// Smart-wallet factories followed by the indexer.
+pub const FACTORIES: &[Factory] = &[Factory {
+    // CA4JRRQ52GDJGWIWE7W6J4AUDGLYSEEUUYM4OXERVQ7AUFGS72YNIF65
+    id: [
+        56, 152, 198, 29, 209, 134, 147, 89, 22, 39, 237, 228, 240, 20, 25, 151, 137, 16, 148, 166, 25, 199, 92, 145,
+        172, 62, 10, 20, 210, 254, 176, 212,
+    ],
+    // the factory keeps the wallet wasm hash in its instance storage.
+    wasm_hash: None,
+    schema: None,
+}];
+
+pub fn by_id(id: [u8; 32]) -> Option<&'static Factory> {
+    FACTORIES.iter().find(|factory| factory.id == id)
+}
+
+#[test]
+fn factory_ids_match_their_strkeys() {
+    let addresses: Vec<String> = FACTORIES.iter().map(Factory::address).collect();
+    assert_eq!(addresses, ["CA4JRRQ52GDJGWIWE7W6J4AUDGLYSEEUUYM4OXERVQ7AUFGS72YNIF65"]);
+}
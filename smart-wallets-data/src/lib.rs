@@ -1,5 +1,32 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use activity::{classify, Activity};
+use balances::TokenBalance;
+use factories::{Factory, FACTORIES};
+use pagination::{event_seq, Page, Paginated};
 use serde::{Deserialize, Serialize};
-use zephyr_sdk::{prelude::*, soroban_sdk::{xdr::{Hash, PublicKey, ScAddress, ScVal, ScVec, VecM}, Address, Bytes, BytesN, String as SorobanString, Symbol}, DatabaseDerive, EnvClient};
+use zephyr_sdk::{prelude::*, soroban_sdk::{xdr::{ContractExecutable, Hash, LedgerEntryData, PublicKey, ScAddress, ScVal, ScVec, VecM}, Bytes}, DatabaseDerive, EntryChanges, EnvClient, TableRow};
+
+mod activity;
+mod balances;
+mod factories;
+mod pagination;
+
+// Smart wallets deployed through the configured factories.
+#[derive(DatabaseDerive, Clone, Serialize)]
+#[with_name("wallets")]
+pub struct Wallets {
+    address: String,
+    /// Strkey of the factory the wallet comes from.
+    factory: String,
+    /// Creation ledger, or the ledger the wallet was first seen in when it
+    /// was created before indexing started.
+    ledger: u32,
+    /// Hex hash of the creation transaction, empty if unknown.
+    tx: String,
+    /// Id of the initial signer, empty if unknown.
+    signer: Vec<u8>,
+}
 
 #[derive(DatabaseDerive, Clone, Serialize)]
 #[with_name("signers")]
@@ -7,7 +34,61 @@ pub struct Signers {
     address: String,
     id: Vec<u8>,
     pubkey: Vec<u8>,
-    active: i32
+    active: i32,
+    /// `ed25519`, `secp256r1` (passkeys) or `policy`.
+    kind: String,
+    /// Sequence of the `add_sig` event, see `event_seq`.
+    seq: u64,
+}
+
+// Narrow views of `signers` and `adjacent`, to read the columns they had from
+// the start and to fill the ones added later in `migrate`.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("signers")]
+pub struct SignerAddress {
+    address: String,
+}
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("signers")]
+pub struct SignerState {
+    pubkey: Vec<u8>,
+    active: i32,
+}
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("signers")]
+pub struct SignerKind {
+    kind: String,
+}
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("signers")]
+pub struct SignerSeq {
+    seq: u64,
+}
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("adjacent")]
+pub struct AdjacentSeq {
+    seq: u64,
+}
+
+// Append-only log of signer additions and removals. `signers` is the current
+// state resulting from it.
+#[derive(DatabaseDerive, Clone, Serialize)]
+#[with_name("sigevents")]
+pub struct SignerEvents {
+    address: String,
+    id: Vec<u8>,
+    pubkey: Vec<u8>,
+    /// `add` or `remove`.
+    action: String,
+    ledger: u32,
+    timestamp: u64,
+    tx: String,
+    /// See `event_seq`.
+    seq: u64,
 }
 
 #[derive(DatabaseDerive, Clone, Serialize)]
@@ -17,60 +98,64 @@ pub struct AdjacentEvents {
     contract: String,
     topics: ScVal,
     data: ScVal,
+    /// See `event_seq`.
+    seq: u64,
 }
 
-fn to_store(existing_addresses: &Vec<String>, topics: &VecM<ScVal>, data: &ScVal) -> Vec<String> {
-    let mut addresses: Vec<String> = Vec::new();
-
-    for topic in topics.to_vec() {
-        for address in existing_addresses {
-            if find_address_in_scval(&topic, stellar_strkey::Contract::from_string(&address).unwrap().0) {
-                addresses.push(address.clone())
-            }
-        }
+/// Tracked wallets involved in an event. Each topic and the data are traversed
+/// once, and the addresses found are looked up in `tracked`.
+fn to_store(tracked: &HashSet<[u8; 32]>, topics: &VecM<ScVal>, data: &ScVal) -> Vec<[u8; 32]> {
+    let mut found: Vec<[u8; 32]> = Vec::new();
+    for topic in topics.iter() {
+        collect_addresses(topic, &mut found);
     }
+    collect_addresses(data, &mut found);
 
-    for address in existing_addresses {
-        if find_address_in_scval(data, stellar_strkey::Contract::from_string(&address).unwrap().0) {
-            addresses.push(address.clone())
+    let mut addresses: Vec<[u8; 32]> = Vec::new();
+    for address in found {
+        if tracked.contains(&address) && !addresses.contains(&address) {
+            addresses.push(address)
         }
     }
 
     addresses
 }
 
-fn find_address_in_scval(val: &ScVal, address: [u8; 32]) -> bool {
+/// Pushes the ed25519 key or contract hash of every address within `val`.
+fn collect_addresses(val: &ScVal, found: &mut Vec<[u8; 32]>) {
     match val {
         ScVal::Address(object) => {
             match object {
                 ScAddress::Account(pubkey) => {
-                    if let PublicKey::PublicKeyTypeEd25519(pubkey) = &pubkey.0 {
-                        return pubkey.0 == address;
-                    }
+                    let PublicKey::PublicKeyTypeEd25519(pubkey) = &pubkey.0;
+                    found.push(pubkey.0)
                 }
                 ScAddress::Contract(hash) => {
-                    return hash.0 == address;
+                    found.push(hash.0)
                 }
             }
         }
         ScVal::Vec(Some(scvec)) => {
-            for val in scvec.0.to_vec() {
-                if find_address_in_scval(&val, address) {
-                    return true;
-                }
+            for val in scvec.0.iter() {
+                collect_addresses(val, found)
             }
         }
         ScVal::Map(Some(scmap)) => {
-            for kv in scmap.0.to_vec() {
-                if find_address_in_scval(&kv.key, address) || find_address_in_scval(&kv.val, address) {
-                    return true;
-                }
+            for kv in scmap.0.iter() {
+                collect_addresses(&kv.key, found);
+                collect_addresses(&kv.val, found);
             }
         }
         _ => {}
     }
+}
 
-    false
+#[cfg(test)]
+fn find_address_in_scval(val: &ScVal, address: [u8; 32]) -> bool {
+    let mut found = Vec::new();
+    collect_addresses(val, &mut found);
+
+    found.contains(&address)
 }
 
 #[test]
@@ -100,90 +185,400 @@ fn bytes_to_vec(bytes: Bytes) -> Vec<u8> {
     result
 }
 
-fn bytesn_to_vec(bytes: BytesN<65>) -> Vec<u8> {
-    let mut result = Vec::new();
-    
-    for byte in bytes.iter() {
-        result.push(byte);
+/// Kind and public key (or policy contract id) of the signer added by an
+/// `add_sig` event. The data is either the bare key, a policy address, or a
+/// signer enum variant such as `Secp256r1(key)`.
+fn signer_key(data: &ScVal) -> Option<(&'static str, Vec<u8>)> {
+    match data {
+        ScVal::Bytes(bytes) if bytes.0.len() == 32 => Some(("ed25519", bytes.0.to_vec())),
+        ScVal::Bytes(bytes) if bytes.0.len() == 65 => Some(("secp256r1", bytes.0.to_vec())),
+        ScVal::Address(ScAddress::Account(account)) => {
+            let PublicKey::PublicKeyTypeEd25519(pubkey) = &account.0;
+            Some(("ed25519", pubkey.0.to_vec()))
+        }
+        ScVal::Address(ScAddress::Contract(hash)) => Some(("policy", hash.0.to_vec())),
+        ScVal::Vec(Some(variant)) => {
+            let Some(ScVal::Symbol(tag)) = variant.0.first() else {
+                return None;
+            };
+            let expected = match tag.0.to_utf8_string_lossy().as_str() {
+                "Ed25519" => "ed25519",
+                "Secp256r1" => "secp256r1",
+                "Policy" => "policy",
+                _ => return None,
+            };
+
+            // the key is the first value of the variant with a known shape,
+            // e.g. `Secp256r1(id, key)` has the id first.
+            variant.0.iter().skip(1).filter_map(signer_key).find(|(kind, _)| *kind == expected)
+        }
+        _ => None,
     }
+}
 
-    result
+#[test]
+fn signer_kinds() {
+    use zephyr_sdk::soroban_sdk::xdr::{ScBytes, ScSymbol};
+
+    let passkey = ScVal::Bytes(ScBytes(vec![4; 65].try_into().unwrap()));
+    assert_eq!(signer_key(&passkey), Some(("secp256r1", vec![4; 65])));
+
+    let ed25519 = ScVal::Bytes(ScBytes(vec![1; 32].try_into().unwrap()));
+    assert_eq!(signer_key(&ed25519), Some(("ed25519", vec![1; 32])));
+
+    let policy = ScVal::Address(ScAddress::Contract(Hash([3; 32])));
+    assert_eq!(signer_key(&policy), Some(("policy", vec![3; 32])));
+
+    let variant = ScVal::Vec(Some(ScVec([ScVal::Symbol(ScSymbol("Secp256r1".try_into().unwrap())), ScVal::Bytes(ScBytes(vec![9; 16].try_into().unwrap())), passkey].try_into().unwrap())));
+    assert_eq!(signer_key(&variant), Some(("secp256r1", vec![4; 65])));
+
+    assert_eq!(signer_key(&ScVal::U32(1)), None);
+    assert_eq!(signer_key(&ScVal::Bytes(ScBytes(vec![1; 20].try_into().unwrap()))), None);
 }
 
 
+/// Wallets created in this ledger from a factory's wallet wasm.
+fn created_wallets(env: &EnvClient) -> Vec<([u8; 32], &'static Factory)> {
+    let mut hashes: Vec<([u8; 32], &'static Factory)> = Vec::new();
+    for factory in FACTORIES {
+        match factory.wallet_wasm(env) {
+            Some(hash) => hashes.push((hash, factory)),
+            None => env.log().debug(format!("no wallet wasm hash for factory {}", factory.address()), None),
+        }
+    }
+    if hashes.is_empty() {
+        return Vec::new();
+    }
+    let EntryChanges { created, .. } = env.reader().v1_success_ledger_entries();
+
+    let mut wallets = Vec::new();
+    for entry in created {
+        if let LedgerEntryData::ContractData(data) = entry.data {
+            if let (ScAddress::Contract(contract), ScVal::ContractInstance(instance)) = (&data.contract, &data.val) {
+                if let ContractExecutable::Wasm(hash) = &instance.executable {
+                    if let Some((_, factory)) = hashes.iter().find(|(wasm_hash, _)| hash.0 == *wasm_hash) {
+                        wallets.push((contract.0, *factory))
+                    }
+                }
+            }
+        }
+    }
+
+    wallets
+}
+
+/// Wallets involved in the ledger's events are looked up in `tracked`, which
+/// discovered wallets join right away so that later events of the same ledger
+/// involving them are stored too. Wallets not yet in the `wallets` table are
+/// written once the ledger is processed, so their initial signer and
+/// transaction can be filled from later events.
+struct NewWallets {
+    tracked: HashSet<[u8; 32]>,
+    wallets: HashMap<[u8; 32], Wallets>,
+}
+
+impl NewWallets {
+    fn discover(&mut self, known: &HashSet<[u8; 32]>, wallet: [u8; 32], factory: &Factory, ledger: u32, tx: String, signer: Vec<u8>) {
+        self.tracked.insert(wallet);
+        if known.contains(&wallet) {
+            return;
+        }
+
+        match self.wallets.entry(wallet) {
+            Entry::Occupied(mut entry) => {
+                let wallet = entry.get_mut();
+                if wallet.tx.is_empty() {
+                    wallet.tx = tx;
+                }
+                if wallet.signer.is_empty() {
+                    wallet.signer = signer;
+                }
+            }
+            Entry::Vacant(entry) => {
+                let address = stellar_strkey::Contract(wallet).to_string();
+                entry.insert(Wallets { address, factory: factory.address(), ledger, tx, signer });
+            }
+        }
+    }
+}
+
+fn symbol_name(val: &ScVal) -> Option<String> {
+    let ScVal::Symbol(symbol) = val else {
+        return None;
+    };
+
+    Some(symbol.0.to_utf8_string_lossy())
+}
+
 #[no_mangle]
 pub extern "C" fn on_close() {
     let env = EnvClient::new();
-    let existing_addresses: Vec<String> = Signers::read_to_rows(&env, None).iter().map(|signer| signer.address.clone()).collect();
-    let factory_address = SorobanString::from_str(&env.soroban(), "CA4JRRQ52GDJGWIWE7W6J4AUDGLYSEEUUYM4OXERVQ7AUFGS72YNIF65");
+    let ledger = env.reader().ledger_sequence();
+    let timestamp = env.reader().ledger_timestamp();
+
+    // Wallets indexed before the `wallets` table existed are only known
+    // through their signers.
+    let known_wallets: HashSet<[u8; 32]> = Wallets::read_to_rows(&env, None)
+        .iter()
+        .filter_map(|wallet| stellar_strkey::Contract::from_string(&wallet.address).ok())
+        .map(|contract| contract.0)
+        .collect();
+    let tracked: HashSet<[u8; 32]> = SignerAddress::read_to_rows(&env, None)
+        .iter()
+        .filter_map(|signer| stellar_strkey::Contract::from_string(&signer.address).ok())
+        .map(|contract| contract.0)
+        .chain(known_wallets.iter().copied())
+        .collect();
+
+    let mut new_wallets = NewWallets { tracked, wallets: HashMap::new() };
+    for (wallet, factory) in created_wallets(&env) {
+        new_wallets.discover(&known_wallets, wallet, factory, ledger, String::new(), Vec::new());
+    }
 
-    for event in env.reader().pretty().soroban_events() {
+    // rows already written for this ledger when it's replayed, e.g. while
+    // catching up. Every adjacent event also has its activity row.
+    let written_signer_events: HashSet<u64> = env
+        .read_filter()
+        .column_equal_to("ledger", ledger)
+        .read::<SignerEvents>()
+        .unwrap()
+        .into_iter()
+        .map(|event| event.seq)
+        .collect();
+    let written_activity: HashSet<(String, u64)> = env
+        .read_filter()
+        .column_equal_to("ledger", ledger)
+        .read::<Activity>()
+        .unwrap()
+        .into_iter()
+        .map(|activity| (activity.address, activity.id))
+        .collect();
+
+    for (index, (event, txhash)) in env.reader().pretty().soroban_events_and_txhash().into_iter().enumerate() {
         // if there are events where the address of the wallet is involved in, we track them.
         // This allows us to track all kinds of operations performed by the smart wallets (transfers, 
         // swaps, deposits, etc).
         {
-            let addresses = to_store(&existing_addresses, &event.topics, &event.data); 
-            
-            for address in addresses {
+            let seq = event_seq(ledger, index);
+            let is_written = |wallet: &[u8; 32]| written_activity.contains(&(stellar_strkey::Contract(*wallet).to_string(), seq));
+            let addresses: Vec<[u8; 32]> = to_store(&new_wallets.tracked, &event.topics, &event.data)
+                .into_iter()
+                .filter(|wallet| !is_written(wallet))
+                .collect();
+
+            for address in addresses.iter() {
                 let event = AdjacentEvents {
                     contract: stellar_strkey::Contract(event.contract).to_string(),
                     topics: ScVal::Vec(Some(ScVec(event.topics.clone().try_into().unwrap()))),
                     data: event.data.clone(),
-                    address
+                    address: stellar_strkey::Contract(*address).to_string(),
+                    seq: event_seq(ledger, index),
                 };
 
                 env.put(&event)
             }
+
+            // the activity feed also covers the wallet's own events (e.g. signer changes).
+            let mut involved = addresses;
+            if new_wallets.tracked.contains(&event.contract) && !involved.contains(&event.contract) && !is_written(&event.contract) {
+                involved.push(event.contract)
+            }
+
+            for wallet in involved {
+                let classified = classify(wallet, event.contract, &event.topics, &event.data);
+                env.put(&Activity {
+                    id: event_seq(ledger, index),
+                    address: stellar_strkey::Contract(wallet).to_string(),
+                    kind: classified.kind.into(),
+                    token: classified.token,
+                    cparty: classified.cparty,
+                    amount: classified.amount,
+                    tx: hex::encode(txhash),
+                    ledger,
+                })
+            }
         };
 
-        if let Some(topic0) = event.topics.get(0) {
-            let factory = env.try_from_scval::<Address>(topic0);
-            if let Ok(factory) = factory {
-                if let Some(topic1) = event.topics.get(1) {
-                    let event_type = env.try_from_scval::<Symbol>(&topic1);
-                    if let Ok(etype) = event_type {
-                        if factory.to_string() == factory_address {
-                            env.log().debug("Found factory", None);
-                            
-                            if etype == Symbol::new(env.soroban(), "add_sig") {
-                                let id: Bytes = env.from_scval(&event.topics[2]);
-                                let pk: BytesN<65> = env.from_scval(&event.data);
-
-                                env.log().debug("creating signer", None);
-                                let signer = Signers {
-                                    address: stellar_strkey::Contract(event.contract).to_string(),
-                                    id: bytes_to_vec(id),
-                                    pubkey: bytesn_to_vec(pk),
-                                    active: 0
-                                };
-                                env.log().debug("created signer", None);
-
-                                env.put(&signer);
-                            } if etype == Symbol::new(env.soroban(), "rm_sig") {
-                                let id: Bytes = env.from_scval(&event.topics[2]);
-                                let id = bytes_to_vec(id);
-                                let older: Vec<Signers> = env.read_filter().column_equal_to("id", id.clone()).column_equal_to("active", 0).read().unwrap();
-                                let mut older = older[0].clone();
-                                older.active = 1;
-
-                                env.update().column_equal_to("id", id).execute(&older).unwrap();
-                            }
-                        }
-                    }
-                }
+        // deploy events are emitted by the factory itself.
+        if let Some(factory) = factories::by_id(event.contract) {
+            let is_deploy = event.topics.first().and_then(symbol_name).as_deref() == Some(factory.schema().deploy);
+            let wallet = event.topics.iter().skip(1).find_map(|topic| match topic {
+                ScVal::Address(ScAddress::Contract(Hash(id))) => Some(*id),
+                _ => None,
+            });
+
+            if let (true, Some(wallet)) = (is_deploy, wallet) {
+                let signer = env.try_from_scval::<Bytes>(&event.data).map(bytes_to_vec).unwrap_or_default();
+                new_wallets.discover(&known_wallets, wallet, factory, ledger, hex::encode(txhash), signer);
             }
         }
+
+        // signer events are emitted by the wallets, with their factory as first topic.
+        let factory = match event.topics.first() {
+            Some(ScVal::Address(ScAddress::Contract(Hash(id)))) => factories::by_id(*id),
+            _ => None,
+        };
+        let (Some(factory), Some(etype)) = (factory, event.topics.get(1).and_then(symbol_name)) else {
+            continue;
+        };
+        env.log().debug("Found factory", None);
+
+        if etype == factory.schema().add_signer {
+            let Some(Ok(id)) = event.topics.get(2).map(|topic| env.try_from_scval::<Bytes>(topic)) else {
+                env.log().debug(format!("skipping signer event without an id {:?}", event.topics), None);
+                continue;
+            };
+            let Some((kind, pk)) = signer_key(&event.data) else {
+                env.log().debug(format!("skipping signer with unknown shape {:?}", event.data), None);
+                continue;
+            };
+
+            env.log().debug("creating signer", None);
+            let signer = Signers {
+                address: stellar_strkey::Contract(event.contract).to_string(),
+                id: bytes_to_vec(id),
+                pubkey: pk,
+                active: 0,
+                kind: kind.into(),
+                seq: event_seq(ledger, index),
+            };
+            env.log().debug("created signer", None);
+
+            new_wallets.discover(&known_wallets, event.contract, factory, ledger, hex::encode(txhash), signer.id.clone());
+            if written_signer_events.contains(&signer.seq) {
+                continue;
+            }
+            env.put(&SignerEvents {
+                address: signer.address.clone(),
+                id: signer.id.clone(),
+                pubkey: signer.pubkey.clone(),
+                action: "add".into(),
+                ledger,
+                timestamp,
+                tx: hex::encode(txhash),
+                seq: event_seq(ledger, index),
+            });
+            env.put(&signer);
+        } else if etype == factory.schema().remove_signer {
+            let Some(Ok(id)) = event.topics.get(2).map(|topic| env.try_from_scval::<Bytes>(topic)) else {
+                env.log().debug(format!("skipping signer event without an id {:?}", event.topics), None);
+                continue;
+            };
+            if written_signer_events.contains(&event_seq(ledger, index)) {
+                continue;
+            }
+            let id = bytes_to_vec(id);
+            // signer ids are only unique within a wallet.
+            let address = stellar_strkey::Contract(event.contract).to_string();
+            let older: Vec<SignerState> = env
+                .read_filter()
+                .column_equal_to("address", address.clone())
+                .column_equal_to("id", id.clone())
+                .column_equal_to("active", 0)
+                .read()
+                .unwrap();
+            let Some(older) = older.first() else {
+                env.log().debug("removed signer is not active, skipping", None);
+                continue;
+            };
+            let mut older = older.clone();
+            older.active = 1;
+
+            env.put(&SignerEvents {
+                address: address.clone(),
+                id: id.clone(),
+                pubkey: older.pubkey.clone(),
+                action: "remove".into(),
+                ledger,
+                timestamp,
+                tx: hex::encode(txhash),
+                seq: event_seq(ledger, index),
+            });
+            env.update()
+                .column_equal_to("address", address)
+                .column_equal_to("id", id)
+                .column_equal_to("active", 0)
+                .execute(&older)
+                .unwrap();
+        }
+    }
+
+    for wallet in new_wallets.wallets.into_values() {
+        env.put(&wallet)
     }
 }            
 
+/// Whether a raw column holds a value. Columns added after a row was written
+/// are empty for it.
+fn is_set(row: &TableRow, index: usize) -> bool {
+    row.row.get(index).is_some_and(|column| bincode::deserialize::<ZephyrVal>(&column.0).is_ok())
+}
+
+/// Sets `column` to `value` on the rows of `table` written before the column
+/// existed, matching them on `keys`. Returns the number of rows updated.
+fn backfill(env: &EnvClient, table: &str, keys: &[&str], column: &str, value: &impl DatabaseInteract) -> usize {
+    let mut columns = keys.to_vec();
+    columns.push(column);
+    let rows = env.db_read(table, &columns, None, None).unwrap();
+
+    let mut updated = 0;
+    for row in rows.rows.iter().filter(|row| !is_set(row, keys.len())) {
+        let mut update = env.update();
+        for (index, key) in keys.iter().enumerate() {
+            update.column_equal_to_bytes(*key, &row.row[index].0);
+        }
+        update.execute(value).unwrap();
+        updated += 1;
+    }
+
+    updated
+}
+
+/// Fills the columns added to `signers` and `adjacent` for the existing rows.
+/// Run once after deploying, before any of them is read in full.
+#[no_mangle]
+pub extern "C" fn migrate() {
+    let env = EnvClient::empty();
+    let signer_keys = ["address", "id", "pubkey", "active"];
+
+    // only passkeys were indexed back then, and the order of their events
+    // wasn't kept.
+    let kinds = backfill(&env, "signers", &signer_keys, "kind", &SignerKind { kind: "secp256r1".into() });
+    let signers = backfill(&env, "signers", &signer_keys, "seq", &SignerSeq { seq: 0 });
+    let adjacent = backfill(&env, "adjacent", &["address", "contract", "topics", "data"], "seq", &AdjacentSeq { seq: 0 });
+
+    env.conclude(format!("migrated {} signer kinds, {} signer sequences and {} adjacent sequences", kinds, signers, adjacent))
+}
+
 #[derive(Deserialize)]
 pub struct QueryByAddressRequest {
-    address: String
+    address: String,
+    #[serde(flatten)]
+    page: Page,
 }
 
 #[derive(Deserialize)]
 pub struct AddressBySignerRequest {
-    id: Vec<u8>
+    id: Vec<u8>,
+    #[serde(flatten)]
+    page: Page,
+}
+
+#[no_mangle]
+pub extern "C" fn get_wallets() {
+    let env = EnvClient::empty();
+    let wallets: Vec<Wallets> = env.read();
+
+    env.conclude(&wallets)
+}
+
+#[no_mangle]
+pub extern "C" fn get_activity() {
+    let env = EnvClient::empty();
+    let request: QueryByAddressRequest = env.read_request_body();
+    let activity: Vec<Activity> = env.read_filter().column_equal_to("address", request.address).read().unwrap();
+
+    env.conclude(request.page.apply(activity, |entry| entry.id))
 }
 
 #[no_mangle]
@@ -191,15 +586,27 @@ pub extern "C" fn get_signers_by_address() {
     let env = EnvClient::empty();
     let request: QueryByAddressRequest = env.read_request_body();
     let signers: Vec<Signers> = env.read_filter().column_equal_to("address", request.address).column_equal_to("active", 0).read().unwrap();
+    let signers: Paginated<Signers> = request.page.apply(signers, |signer| signer.seq);
 
     env.conclude(&signers)
 }
 
+#[no_mangle]
+pub extern "C" fn get_signer_history() {
+    let env = EnvClient::empty();
+    let request: QueryByAddressRequest = env.read_request_body();
+    let events: Vec<SignerEvents> = env.read_filter().column_equal_to("address", request.address).read().unwrap();
+    let events: Paginated<SignerEvents> = request.page.apply(events, |event| event.seq);
+
+    env.conclude(&events)
+}
+
 #[no_mangle]
 pub extern "C" fn get_address_by_signer() {
     let env = EnvClient::empty();
     let request: AddressBySignerRequest = env.read_request_body();
     let signers: Vec<Signers> = env.read_filter().column_equal_to("id", request.id).column_equal_to("active", 0).read().unwrap();
+    let signers: Paginated<Signers> = request.page.apply(signers, |signer| signer.seq);
 
     env.conclude(&signers)
 }
@@ -209,6 +616,33 @@ pub extern "C" fn get_events_by_address() {
     let env = EnvClient::empty();
     let request: QueryByAddressRequest = env.read_request_body();
     let events: Vec<AdjacentEvents> = env.read_filter().column_equal_to("address", request.address).read().unwrap();
+    let events: Paginated<AdjacentEvents> = request.page.apply(events, |event| event.seq);
 
     env.conclude(&events)
 }
+
+#[derive(Deserialize)]
+pub struct BalancesRequest {
+    address: String,
+    /// Token contract strkeys.
+    tokens: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct BalancesError {
+    error: String,
+}
+
+#[no_mangle]
+pub extern "C" fn get_balances() {
+    let env = EnvClient::empty();
+    let request: BalancesRequest = env.read_request_body();
+    let holder = match balances::holder(&request.address) {
+        Ok(holder) => holder,
+        Err(error) => return env.conclude(&BalancesError { error }),
+    };
+
+    let balances: Vec<TokenBalance> = request.tokens.iter().map(|token| balances::balance(&env, holder, token)).collect();
+
+    env.conclude(&balances)
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+pub const MAX_PAGE_LIMIT: usize = 200;
+
+/// `(ledger << 32) | event index`, unique and increasing across ledgers.
+/// Rows carry the sequence of the event that created them, and it's used
+/// both as pagination cursor and to filter by ledger.
+pub fn event_seq(ledger: u32, index: usize) -> u64 {
+    ((ledger as u64) << 32) | index as u64
+}
+
+fn ledger_of(seq: u64) -> u32 {
+    (seq >> 32) as u32
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Pagination and filtering parameters shared by the query functions.
+#[derive(Deserialize, Default)]
+pub struct Page {
+    /// `next` of the previous page, omitted for the first one.
+    cursor: Option<u64>,
+    limit: Option<usize>,
+    /// Inclusive ledger range.
+    from_ledger: Option<u32>,
+    to_ledger: Option<u32>,
+    /// Newest first by default.
+    #[serde(default)]
+    order: Order,
+}
+
+#[derive(Serialize)]
+pub struct Paginated<T> {
+    items: Vec<T>,
+    /// Cursor of the next page, `None` when this is the last one.
+    next: Option<u64>,
+}
+
+impl Page {
+    pub fn apply<T>(&self, mut rows: Vec<T>, seq: impl Fn(&T) -> u64) -> Paginated<T> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+
+        rows.retain(|row| {
+            let seq = seq(row);
+            let ledger = ledger_of(seq);
+
+            self.from_ledger.is_none_or(|from| ledger >= from)
+                && self.to_ledger.is_none_or(|to| ledger <= to)
+                && self.cursor.is_none_or(|cursor| match self.order {
+                    Order::Asc => seq > cursor,
+                    Order::Desc => seq < cursor,
+                })
+        });
+        rows.sort_by_key(|row| seq(row));
+        if self.order == Order::Desc {
+            rows.reverse();
+        }
+
+        let next = if rows.len() > limit { Some(seq(&rows[limit - 1])) } else { None };
+        rows.truncate(limit);
+
+        Paginated { items: rows, next }
+    }
+}
+
+#[test]
+fn pages() {
+    let rows: Vec<u64> = (1..=5).map(|idx| event_seq(10 + idx as u32, 0)).collect();
+
+    let page = Page { limit: Some(2), ..Default::default() };
+    let first = page.apply(rows.clone(), |row| *row);
+    assert_eq!(first.items, vec![event_seq(15, 0), event_seq(14, 0)]);
+    assert_eq!(first.next, Some(event_seq(14, 0)));
+
+    let page = Page { limit: Some(2), cursor: first.next, ..Default::default() };
+    let second = page.apply(rows.clone(), |row| *row);
+    assert_eq!(second.items, vec![event_seq(13, 0), event_seq(12, 0)]);
+
+    let page = Page { order: Order::Asc, from_ledger: Some(12), to_ledger: Some(13), ..Default::default() };
+    let ranged = page.apply(rows, |row| *row);
+    assert_eq!(ranged.items, vec![event_seq(12, 0), event_seq(13, 0)]);
+    assert_eq!(ranged.next, None);
+}
@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use zephyr_sdk::EnvClient;
+
+use crate::{
+    rollups::Rollup,
+    utils::{format_amount, iso_date, iso_hour},
+};
+
+/// Raw (unscaled) volume by protocol.
+#[derive(Default, Clone)]
+pub struct Volume(BTreeMap<String, i128>);
+
+impl Volume {
+    pub fn add(&mut self, protocol: &str, amount: i128) {
+        *self.0.entry(protocol.to_string()).or_insert(0) += amount;
+    }
+
+    pub fn get(&self, protocol: &str) -> i128 {
+        self.0.get(protocol).copied().unwrap_or(0)
+    }
+
+    pub fn formatted(&self, decimals: u32) -> BTreeMap<String, String> {
+        formatted(&self.0, decimals)
+    }
+}
+
+fn formatted(values: &BTreeMap<String, i128>, decimals: u32) -> BTreeMap<String, String> {
+    values
+        .iter()
+        .map(|(protocol, value)| (protocol.clone(), format_amount(*value, decimals)))
+        .collect()
+}
+
+/// Raw (unscaled) TVL history by protocol.
+pub struct Tvl(pub Vec<(u64, BTreeMap<String, i128>)>);
+
+impl Tvl {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
+
+    /// Sets the TVL of a protocol at the given timestamp, carrying over the
+    /// other protocols' values from the previous point.
+    pub fn set(&mut self, protocol: &str, value: i128, timestamp: u64) {
+        if let Some((last_timestamp, values)) = self.0.last_mut() {
+            if *last_timestamp == timestamp {
+                values.insert(protocol.to_string(), value);
+                return;
+            }
+        }
+
+        let mut previous_total = self
+            .0
+            .last()
+            .map(|(_, values)| values.clone())
+            .unwrap_or_default();
+        previous_total.insert(protocol.to_string(), value);
+
+        self.0.push((timestamp, previous_total));
+    }
+
+    /// Resamples the history into buckets of `timeframe` seconds, keeping the
+    /// last value of each bucket.
+    pub fn resample(&self, timeframe: u64) -> Vec<(u64, BTreeMap<String, i128>)> {
+        let mut resampled: Vec<(u64, BTreeMap<String, i128>)> = Vec::new();
+        for (timestamp, values) in self.0.iter() {
+            let bucket = timestamp - timestamp % timeframe;
+            match resampled.last_mut() {
+                Some((last_bucket, last_values)) if *last_bucket == bucket => {
+                    *last_values = values.clone()
+                }
+                _ => resampled.push((bucket, values.clone())),
+            }
+        }
+
+        resampled
+    }
+
+    pub fn current(&self, protocol: &str) -> i128 {
+        self.0
+            .last()
+            .and_then(|(_, values)| values.get(protocol).copied())
+            .unwrap_or(0)
+    }
+
+    pub fn current_total(&self) -> i128 {
+        self.0
+            .last()
+            .map(|(_, values)| values.values().sum())
+            .unwrap_or(0)
+    }
+}
+
+pub const HOUR_TIMEFRAME: u64 = 3_600;
+pub const DAY_TIMEFRAME: u64 = 86_400;
+pub const WEEK_TIMEFRAME: u64 = DAY_TIMEFRAME * 7;
+pub const MONTH_TIMEFRAME: u64 = DAY_TIMEFRAME * 30;
+
+/// Granularity of the TVL history chart.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl Resolution {
+    pub fn timeframe(self) -> u64 {
+        match self {
+            Resolution::Hourly => HOUR_TIMEFRAME,
+            Resolution::Daily => DAY_TIMEFRAME,
+        }
+    }
+
+    pub fn label(self, timestamp: u64) -> String {
+        match self {
+            Resolution::Hourly => iso_hour(timestamp),
+            Resolution::Daily => iso_date(timestamp),
+        }
+    }
+}
+
+pub struct AssetAggregation {
+    pub vol_24_hrs: Volume,
+    pub vol_week: Volume,
+    pub vol_month: Volume,
+    pub total_volume: Volume,
+    pub total_value: Tvl,
+}
+
+impl AssetAggregation {
+    pub fn new() -> Self {
+        Self {
+            vol_24_hrs: Volume::default(),
+            vol_week: Volume::default(),
+            vol_month: Volume::default(),
+            total_volume: Volume::default(),
+            total_value: Tvl::new(),
+        }
+    }
+
+    fn update_volumes(
+        &mut self,
+        current_timestamp: u64,
+        protocol: &str,
+        amount: i128,
+        timestamp: u64,
+    ) {
+        self.total_volume.add(protocol, amount);
+
+        if timestamp + DAY_TIMEFRAME > current_timestamp {
+            self.vol_24_hrs.add(protocol, amount)
+        }
+
+        if timestamp + WEEK_TIMEFRAME > current_timestamp {
+            self.vol_week.add(protocol, amount)
+        }
+
+        if timestamp + MONTH_TIMEFRAME > current_timestamp {
+            self.vol_month.add(protocol, amount)
+        }
+    }
+
+    pub fn add(&mut self, current_timestamp: u64, rollup: &Rollup) {
+        self.update_volumes(
+            current_timestamp,
+            &rollup.protocol,
+            rollup.volume,
+            rollup.bucket,
+        );
+        self.total_value
+            .set(&rollup.protocol, rollup.tvl, rollup.bucket);
+    }
+
+    pub fn stats(&self, asset: String, symbol: String, decimals: u32) -> AssetStats {
+        AssetStats {
+            asset,
+            symbol,
+            decimals,
+            total_volume: self.total_volume.formatted(decimals),
+            month_volume: self.vol_month.formatted(decimals),
+            week_volume: self.vol_week.formatted(decimals),
+            day_volume: self.vol_24_hrs.formatted(decimals),
+            tvl: self
+                .total_value
+                .0
+                .last()
+                .map(|(_, values)| formatted(values, decimals))
+                .unwrap_or_default(),
+            tvl_history: self
+                .total_value
+                .0
+                .iter()
+                .map(|(timestamp, values)| TvlPoint {
+                    timestamp: *timestamp,
+                    total: format_amount(values.values().sum(), decimals),
+                    protocols: formatted(values, decimals),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TvlPoint {
+    pub timestamp: u64,
+    pub total: String,
+    pub protocols: BTreeMap<String, String>,
+}
+
+/// Volumes and TVL of an asset as returned by the JSON API. Values are
+/// exact decimal strings keyed by protocol name.
+#[derive(Serialize)]
+pub struct AssetStats {
+    pub asset: String,
+    pub symbol: String,
+    pub decimals: u32,
+    pub total_volume: BTreeMap<String, String>,
+    pub month_volume: BTreeMap<String, String>,
+    pub week_volume: BTreeMap<String, String>,
+    pub day_volume: BTreeMap<String, String>,
+    pub tvl: BTreeMap<String, String>,
+    pub tvl_history: Vec<TvlPoint>,
+}
+
+/// Builds the per-asset aggregations from the rollups table.
+pub fn aggregate(env: &EnvClient) -> HashMap<String, AssetAggregation> {
+    let mut map = HashMap::new();
+    let current_timestamp = env.soroban().ledger().timestamp();
+
+    let mut rollups: Vec<Rollup> = env.read();
+    rollups.sort_by_key(|rollup| rollup.bucket);
+    for rollup in rollups.iter() {
+        map.entry(rollup.asset.clone())
+            .or_insert_with(AssetAggregation::new)
+            .add(current_timestamp, rollup);
+    }
+
+    map
+}
@@ -0,0 +1,112 @@
+use zephyr_sdk::{
+    soroban_sdk::{
+        xdr::{LedgerEntryData, ScMapEntry, ScString, ScSymbol, ScVal, ScVec},
+        Symbol,
+    },
+    EnvClient,
+};
+
+/// Decimals assumed when a token doesn't expose them, same as classic assets.
+pub const DEFAULT_DECIMALS: u32 = 7;
+
+const SYMBOL_KEYS: [&str; 3] = ["Symbol", "symbol", "SYMBOL"];
+const DECIMALS_KEYS: [&str; 4] = ["Decimals", "Decimal", "decimals", "decimal"];
+
+pub struct AssetMetadata {
+    pub symbol: String,
+    pub decimals: u32,
+}
+
+/// Resolves symbol and decimals of a token. Tries the SAC (and token SDK)
+/// `METADATA` entry first, then the custom instance layouts used by older
+/// SEP-41 tokens, and falls back to the strkey if the instance can't be read.
+pub fn resolve(env: &EnvClient, asset: &str) -> AssetMetadata {
+    if let Some(storage) = instance_storage(env, asset) {
+        if let Some(metadata) = from_sac_metadata(env, &storage) {
+            return metadata;
+        }
+
+        if let Some(metadata) = from_custom_layout(env, &storage) {
+            return metadata;
+        }
+    }
+
+    env.log()
+        .debug(format!("no metadata found for {}", asset), None);
+    AssetMetadata {
+        symbol: asset.to_string(),
+        decimals: DEFAULT_DECIMALS,
+    }
+}
+
+fn instance_storage(env: &EnvClient, asset: &str) -> Option<Vec<ScMapEntry>> {
+    let contract = stellar_strkey::Contract::from_string(asset).ok()?;
+    let instance = env.read_contract_instance(contract.0).ok()??;
+    let LedgerEntryData::ContractData(data) = instance.entry.data else {
+        return None;
+    };
+    let ScVal::ContractInstance(instance) = data.val else {
+        return None;
+    };
+
+    instance.storage.map(|storage| storage.0.to_vec())
+}
+
+fn from_sac_metadata(env: &EnvClient, storage: &[ScMapEntry]) -> Option<AssetMetadata> {
+    let key = env.to_scval(Symbol::new(env.soroban(), "METADATA"));
+    let entry = storage.iter().find(|entry| entry.key == key)?;
+    // `StellarAssetContractMetadata { decimal, name, symbol }`.
+    let ScVal::Map(Some(fields)) = &entry.val else {
+        return None;
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|field| matches!(&field.key, ScVal::Symbol(ScSymbol(key)) if key.as_slice() == name.as_bytes()))
+            .map(|field| &field.val)
+    };
+    let (Some(ScVal::String(ScString(symbol))), Some(ScVal::U32(decimals))) =
+        (field("symbol"), field("decimal"))
+    else {
+        return None;
+    };
+
+    Some(AssetMetadata {
+        symbol: symbol.to_utf8_string().ok()?,
+        decimals: *decimals,
+    })
+}
+
+/// Tokens storing each field under its own key, either as a bare symbol
+/// (`Symbol("symbol")`) or as a unit enum variant (`DataKey::Symbol`).
+fn from_custom_layout(env: &EnvClient, storage: &[ScMapEntry]) -> Option<AssetMetadata> {
+    let symbol = SYMBOL_KEYS
+        .iter()
+        .find_map(|name| find_by_name(env, storage, name))
+        .and_then(|val| match val {
+            ScVal::String(ScString(s)) => s.to_utf8_string().ok(),
+            ScVal::Symbol(ScSymbol(s)) => s.to_utf8_string().ok(),
+            _ => None,
+        })?;
+
+    let decimals = DECIMALS_KEYS
+        .iter()
+        .find_map(|name| find_by_name(env, storage, name))
+        .and_then(|val| match val {
+            ScVal::U32(decimals) => Some(*decimals),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_DECIMALS);
+
+    Some(AssetMetadata { symbol, decimals })
+}
+
+fn find_by_name<'a>(env: &EnvClient, storage: &'a [ScMapEntry], name: &str) -> Option<&'a ScVal> {
+    let symbol = env.to_scval(Symbol::new(env.soroban(), name));
+    let variant = ScVal::Vec(Some(ScVec([symbol.clone()].try_into().unwrap())));
+
+    storage
+        .iter()
+        .find(|entry| entry.key == symbol || entry.key == variant)
+        .map(|entry| &entry.val)
+}
@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+
+use zephyr_sdk::{
+    charting::Table, prelude::*, soroban_sdk::xdr::ScVal, utils::soroban_string_to_alloc_string,
+    DatabaseDerive, EnvClient,
+};
+
+use crate::{
+    metadata::{self, AssetMetadata},
+    protocols::{contract_strkey, EventsTable},
+    utils::format_amount,
+};
+
+/// Soroswap's swap fee (0.3%), in basis points.
+pub const SWAP_FEE_BPS: i128 = 30;
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("pairs")]
+#[external("9")]
+pub struct PairsTable {
+    pub address: ScVal,
+    pub token_a: ScVal,
+    pub token_b: ScVal,
+    pub reserve_a: ScVal,
+    pub reserve_b: ScVal,
+}
+
+// All-time activity of a Soroswap pair.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("pairstats")]
+pub struct PairStats {
+    pub token_a: String,
+    pub token_b: String,
+    /// Swapped amounts of each token.
+    pub volume_a: i128,
+    pub volume_b: i128,
+    pub swaps: u32,
+    pub adds: u32,
+    pub removes: u32,
+}
+
+impl PairStats {
+    fn new(token_a: String, token_b: String) -> Self {
+        Self {
+            token_a,
+            token_b,
+            volume_a: 0,
+            volume_b: 0,
+            swaps: 0,
+            adds: 0,
+            removes: 0,
+        }
+    }
+
+    fn merge(&mut self, other: &PairStats) {
+        self.volume_a += other.volume_a;
+        self.volume_b += other.volume_b;
+        self.swaps += other.swaps;
+        self.adds += other.adds;
+        self.removes += other.removes;
+    }
+}
+
+/// Folds a ledger's Soroswap events into the `pairstats` rows.
+pub fn ingest(env: &EnvClient, events: &[EventsTable]) {
+    let mut grouped: BTreeMap<(String, String), PairStats> = BTreeMap::new();
+    for event in events {
+        let (Some(token_a), Some(token_b)) = (
+            contract_strkey(&event.token_a),
+            contract_strkey(&event.token_b),
+        ) else {
+            continue;
+        };
+
+        let e_type = soroban_string_to_alloc_string(env, env.from_scval(&event.e_type));
+        let stats = grouped
+            .entry((token_a.clone(), token_b.clone()))
+            .or_insert_with(|| PairStats::new(token_a, token_b));
+
+        if e_type == "swap" {
+            let amount_a: i128 = env.from_scval(&event.amount_a);
+            let amount_b: i128 = env.from_scval(&event.amount_b);
+            stats.swaps += 1;
+            stats.volume_a += amount_a;
+            stats.volume_b += amount_b;
+        } else if e_type == "add" {
+            stats.adds += 1;
+        } else if e_type == "remove" {
+            stats.removes += 1;
+        }
+    }
+
+    for ((token_a, token_b), delta) in grouped {
+        let existing: Vec<PairStats> = env
+            .read_filter()
+            .column_equal_to("token_a", token_a.clone())
+            .column_equal_to("token_b", token_b.clone())
+            .read()
+            .unwrap();
+
+        if let Some(row) = existing.first() {
+            let mut row = row.clone();
+            row.merge(&delta);
+            env.update()
+                .column_equal_to("token_a", token_a)
+                .column_equal_to("token_b", token_b)
+                .execute(&row)
+                .unwrap();
+        } else {
+            env.put(&delta);
+        }
+    }
+}
+
+/// Upper bound of the fees collected in a token, given its swapped volume.
+/// The fee is only taken on the input side of a swap, which the indexed
+/// events don't tell, so the volume of each side is charged in full.
+fn max_fees(volume: i128) -> i128 {
+    volume * SWAP_FEE_BPS / 10_000
+}
+
+/// Pair breakdown table for the dashboard, most active pairs first.
+pub fn table(env: &EnvClient) -> Table {
+    let mut stats: Vec<PairStats> = env.read();
+    stats.sort_by_key(|pair| std::cmp::Reverse(pair.swaps));
+
+    let pair_contracts: Vec<PairsTable> = env.read();
+    let addresses: HashMap<(String, String), String> = pair_contracts
+        .iter()
+        .filter_map(|pair| {
+            Some((
+                (
+                    contract_strkey(&pair.token_a)?,
+                    contract_strkey(&pair.token_b)?,
+                ),
+                contract_strkey(&pair.address)?,
+            ))
+        })
+        .collect();
+
+    let mut metadata_cache: HashMap<String, AssetMetadata> = HashMap::new();
+    let mut table = Table::new().columns(vec![
+        "Pair".into(),
+        "Address".into(),
+        "Volume".into(),
+        "Swaps".into(),
+        "Liquidity adds".into(),
+        "Liquidity removes".into(),
+        "Max. fees".into(),
+    ]);
+
+    for pair in stats {
+        for token in [&pair.token_a, &pair.token_b] {
+            metadata_cache
+                .entry(token.clone())
+                .or_insert_with(|| metadata::resolve(env, token));
+        }
+        let meta_a = &metadata_cache[&pair.token_a];
+        let meta_b = &metadata_cache[&pair.token_b];
+
+        table = table.row(vec![
+            format!("{}/{}", meta_a.symbol, meta_b.symbol),
+            addresses
+                .get(&(pair.token_a.clone(), pair.token_b.clone()))
+                .cloned()
+                .unwrap_or_default(),
+            format!(
+                "{} {}, {} {}",
+                format_amount(pair.volume_a, meta_a.decimals),
+                meta_a.symbol,
+                format_amount(pair.volume_b, meta_b.decimals),
+                meta_b.symbol
+            ),
+            pair.swaps.to_string(),
+            pair.adds.to_string(),
+            pair.removes.to_string(),
+            format!(
+                "{} {}, {} {}",
+                format_amount(max_fees(pair.volume_a), meta_a.decimals),
+                meta_a.symbol,
+                format_amount(max_fees(pair.volume_b), meta_b.decimals),
+                meta_b.symbol
+            ),
+        ]);
+    }
+
+    table
+}
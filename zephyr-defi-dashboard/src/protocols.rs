@@ -0,0 +1,327 @@
+use std::cell::OnceCell;
+
+use zephyr_sdk::{
+    prelude::*,
+    soroban_sdk::xdr::{Hash, ScAddress, ScVal},
+    utils::soroban_string_to_alloc_string,
+    DatabaseDerive, EnvClient,
+};
+
+use crate::pairs;
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("events")]
+#[external("9")]
+pub struct EventsTable {
+    pub e_type: ScVal,
+    pub token_a: ScVal,
+    pub token_b: ScVal,
+    pub amount_a: ScVal,
+    pub amount_b: ScVal,
+    pub account: ScVal,
+    pub timestamp: ScVal,
+}
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("clateral")]
+#[external("8")]
+pub struct Collateral {
+    pub id: i64,
+    pub timestamp: u64,
+    pub ledger: u32,
+    pub pool: String,
+    pub asset: String,
+    pub clateral: i128,
+    pub delta: i128,
+    pub source: String,
+}
+
+#[derive(DatabaseDerive, Clone)]
+#[with_name("borrowed")]
+#[external("8")]
+pub struct Borrowed {
+    pub id: i64,
+    pub timestamp: u64,
+    pub ledger: u32,
+    pub pool: String,
+    pub asset: String,
+    pub borrowed: i128,
+    pub delta: i128,
+    pub source: String,
+}
+
+// FxDAO vault collateral changes (deposits and withdrawals).
+#[derive(DatabaseDerive, Clone)]
+#[with_name("vaults")]
+#[external("14")]
+pub struct FxDaoVaults {
+    pub timestamp: u64,
+    pub ledger: u32,
+    pub asset: String,
+    pub collateral: i128,
+    pub delta: i128,
+    pub source: String,
+}
+
+// Phoenix pool events, same layout as Soroswap's `events` table.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("events")]
+#[external("15")]
+pub struct PhoenixEvents {
+    pub e_type: ScVal,
+    pub token_a: ScVal,
+    pub token_b: ScVal,
+    pub amount_a: ScVal,
+    pub amount_b: ScVal,
+    pub account: ScVal,
+    pub timestamp: ScVal,
+}
+
+// Aquarius pool events, same layout as Soroswap's `events` table.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("events")]
+#[external("16")]
+pub struct AquariusEvents {
+    pub e_type: ScVal,
+    pub token_a: ScVal,
+    pub token_b: ScVal,
+    pub amount_a: ScVal,
+    pub amount_b: ScVal,
+    pub account: ScVal,
+    pub timestamp: ScVal,
+}
+
+/// A change in volume and TVL of a single asset within a protocol.
+pub struct Delta {
+    /// Contract strkey of the asset.
+    pub asset: String,
+    pub timestamp: u64,
+    pub volume: i128,
+    pub tvl: i128,
+}
+
+/// The ledger whose rows are folded on a close.
+#[derive(Clone, Copy)]
+pub struct Ledger {
+    pub sequence: u32,
+    pub timestamp: u64,
+}
+
+/// Rows of a protocol table, read at most once per close since the registry
+/// is built anew on every close.
+pub struct Cached<T>(OnceCell<Vec<T>>);
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Self(OnceCell::new())
+    }
+}
+
+impl<T> Cached<T> {
+    pub fn get_or_read(&self, read: impl FnOnce() -> Vec<T>) -> &[T] {
+        self.0.get_or_init(read)
+    }
+}
+
+/// A protocol tracked by the dashboard. Implementors read the rows their
+/// indexer wrote in a single ledger, filtered at query level, and turn them
+/// into per-asset deltas.
+pub trait Protocol {
+    fn name(&self) -> &'static str;
+
+    fn deltas(&self, env: &EnvClient, ledger: &Ledger) -> Vec<Delta>;
+
+    /// Updates the tables the protocol maintains besides the rollups, from
+    /// the same rows as `deltas`.
+    fn ingest(&self, _env: &EnvClient, _ledger: &Ledger) {}
+}
+
+/// All the protocols the dashboard aggregates, in display order.
+///
+/// Each adapter reads the tables of an existing Mercury program: Blend's
+/// `borrowed`/`clateral` from program 8 and Soroswap's `events` from program
+/// 9, the same sources the dashboard used before the registry. FxDAO (14),
+/// Phoenix (15) and Aquarius (16) are only registered with their feature
+/// enabled, until their indexers' program ids and table layouts are
+/// confirmed, since reading a missing external table aborts the whole close.
+pub fn registry() -> Vec<Box<dyn Protocol>> {
+    let mut protocols: Vec<Box<dyn Protocol>> = vec![Box::new(Blend), Box::<Soroswap>::default()];
+    if cfg!(feature = "fxdao") {
+        protocols.push(Box::new(FxDao));
+    }
+    if cfg!(feature = "phoenix") {
+        protocols.push(Box::<Phoenix>::default());
+    }
+    if cfg!(feature = "aquarius") {
+        protocols.push(Box::<Aquarius>::default());
+    }
+
+    protocols
+}
+
+/// Strkey of a contract address, `None` for any other value.
+pub fn contract_strkey(val: &ScVal) -> Option<String> {
+    let ScVal::Address(ScAddress::Contract(Hash(contract))) = val else {
+        return None;
+    };
+
+    Some(stellar_strkey::Contract(*contract).to_string())
+}
+
+fn amm_delta(
+    env: &EnvClient,
+    token: &ScVal,
+    amount: &ScVal,
+    e_type: &ScVal,
+    timestamp: &ScVal,
+) -> Option<Delta> {
+    let asset = contract_strkey(token)?;
+    let e_type = soroban_string_to_alloc_string(env, env.from_scval(e_type));
+    let amount: i128 = env.from_scval(amount);
+    let timestamp: u64 = env.from_scval(timestamp);
+
+    let tvl = if e_type == "add" {
+        amount
+    } else if e_type == "remove" {
+        -amount
+    } else {
+        0
+    };
+
+    Some(Delta {
+        asset,
+        timestamp,
+        volume: amount,
+        tvl,
+    })
+}
+
+/// AMM events of `ledger`. The indexers store the ledger close time as the
+/// event's `timestamp`, which the rows are filtered on.
+fn amm_events<T: DatabaseInteract>(env: &EnvClient, ledger: &Ledger) -> Vec<T> {
+    env.read_filter()
+        .column_equal_to_xdr("timestamp", &ScVal::U64(ledger.timestamp))
+        .read()
+        .unwrap()
+}
+
+macro_rules! impl_amm {
+    ($struct_name:ident, $table:ident, $name:literal $(, $ingest:path)?) => {
+        #[derive(Default)]
+        pub struct $struct_name {
+            events: Cached<$table>,
+        }
+
+        impl $struct_name {
+            fn events(&self, env: &EnvClient, ledger: &Ledger) -> &[$table] {
+                self.events.get_or_read(|| amm_events(env, ledger))
+            }
+        }
+
+        impl Protocol for $struct_name {
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            fn deltas(&self, env: &EnvClient, ledger: &Ledger) -> Vec<Delta> {
+                let mut deltas = Vec::new();
+                for event in self.events(env, ledger) {
+                    deltas.extend(amm_delta(
+                        env,
+                        &event.token_a,
+                        &event.amount_a,
+                        &event.e_type,
+                        &event.timestamp,
+                    ));
+                    deltas.extend(amm_delta(
+                        env,
+                        &event.token_b,
+                        &event.amount_b,
+                        &event.e_type,
+                        &event.timestamp,
+                    ));
+                }
+
+                deltas
+            }
+
+            $(
+                fn ingest(&self, env: &EnvClient, ledger: &Ledger) {
+                    $ingest(env, self.events(env, ledger));
+                }
+            )?
+        }
+    };
+}
+
+impl_amm!(Soroswap, EventsTable, "Soroswap", pairs::ingest);
+impl_amm!(Phoenix, PhoenixEvents, "Phoenix");
+impl_amm!(Aquarius, AquariusEvents, "Aquarius");
+
+pub struct Blend;
+
+impl Protocol for Blend {
+    fn name(&self) -> &'static str {
+        "Blend"
+    }
+
+    fn deltas(&self, env: &EnvClient, ledger: &Ledger) -> Vec<Delta> {
+        let borrowed: Vec<Borrowed> = env
+            .read_filter()
+            .column_equal_to("ledger", ledger.sequence)
+            .read()
+            .unwrap();
+        let collateral: Vec<Collateral> = env
+            .read_filter()
+            .column_equal_to("ledger", ledger.sequence)
+            .read()
+            .unwrap();
+
+        let mut deltas = Vec::new();
+        for borrow in borrowed {
+            deltas.push(Delta {
+                asset: borrow.asset,
+                timestamp: borrow.timestamp,
+                volume: borrow.delta.abs(),
+                tvl: 0,
+            })
+        }
+
+        for collateral in collateral {
+            deltas.push(Delta {
+                asset: collateral.asset,
+                timestamp: collateral.timestamp,
+                volume: collateral.delta.abs(),
+                tvl: collateral.delta,
+            })
+        }
+
+        deltas
+    }
+}
+
+pub struct FxDao;
+
+impl Protocol for FxDao {
+    fn name(&self) -> &'static str {
+        "FxDAO"
+    }
+
+    fn deltas(&self, env: &EnvClient, ledger: &Ledger) -> Vec<Delta> {
+        let vaults: Vec<FxDaoVaults> = env
+            .read_filter()
+            .column_equal_to("ledger", ledger.sequence)
+            .read()
+            .unwrap();
+
+        vaults
+            .into_iter()
+            .map(|vault| Delta {
+                asset: vault.asset,
+                timestamp: vault.timestamp,
+                volume: vault.delta.abs(),
+                tvl: vault.delta,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use zephyr_sdk::{prelude::*, DatabaseDerive, EnvClient};
+
+use crate::protocols::{Delta, Ledger};
+
+pub const BUCKET_TIMEFRAME: u64 = 3_600;
+
+// Volume and TVL of an asset within a protocol, bucketed hourly.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("rollups")]
+pub struct Rollup {
+    pub asset: String,
+    pub protocol: String,
+    /// Start timestamp of the bucket.
+    pub bucket: u64,
+    /// Volume within the bucket.
+    pub volume: i128,
+    /// TVL at the end of the bucket.
+    pub tvl: i128,
+}
+
+// Sequence of the last ledger whose protocol rows were folded into the
+// rollups, so that a replayed close doesn't fold them twice.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("cursors")]
+pub struct Cursor {
+    pub protocol: String,
+    pub last: u32,
+}
+
+impl Cursor {
+    pub fn get(env: &EnvClient, protocol: &str) -> Self {
+        let query = env
+            .read_filter()
+            .column_equal_to("protocol", protocol.to_string())
+            .read();
+        let res: Option<&Self> = query.as_ref().unwrap().first();
+        if let Some(res) = res {
+            res.clone()
+        } else {
+            let default = Cursor {
+                protocol: protocol.to_string(),
+                last: 0,
+            };
+            default.put(env);
+
+            default
+        }
+    }
+
+    pub fn advance(mut self, env: &EnvClient, last: u32) {
+        self.last = last;
+        env.update()
+            .column_equal_to("protocol", self.protocol.clone())
+            .execute(&self)
+            .unwrap();
+    }
+}
+
+// Ledger of the previous close. Its rows are folded on the next close, as
+// the protocols' indexers may run after this program on the same ledger.
+#[derive(DatabaseDerive, Clone)]
+#[with_name("lastclose")]
+pub struct LastClose {
+    pub sequence: u32,
+    pub timestamp: u64,
+}
+
+impl LastClose {
+    /// Records `current` and returns the ledger of the previous close, `None`
+    /// on the first one. Ledgers the program didn't run on are not folded.
+    pub fn swap(env: &EnvClient, current: Ledger) -> Option<Ledger> {
+        let previous = env.read::<Self>().into_iter().next();
+        let row = LastClose {
+            sequence: current.sequence,
+            timestamp: current.timestamp,
+        };
+
+        if let Some(previous) = &previous {
+            env.update()
+                .column_equal_to("sequence", previous.sequence)
+                .execute(&row)
+                .unwrap();
+        } else {
+            env.put(&row);
+        }
+
+        previous.map(|previous| Ledger {
+            sequence: previous.sequence,
+            timestamp: previous.timestamp,
+        })
+    }
+}
+
+pub fn bucket_of(timestamp: u64) -> u64 {
+    timestamp - timestamp % BUCKET_TIMEFRAME
+}
+
+/// Folds new deltas of a protocol into the existing rollup rows.
+pub fn apply(env: &EnvClient, protocol: &str, deltas: &[Delta]) {
+    let mut grouped: BTreeMap<&str, BTreeMap<u64, (i128, i128)>> = BTreeMap::new();
+    for delta in deltas {
+        let (volume, tvl) = grouped
+            .entry(delta.asset.as_str())
+            .or_default()
+            .entry(bucket_of(delta.timestamp))
+            .or_insert((0, 0));
+        *volume += delta.volume;
+        *tvl += delta.tvl;
+    }
+
+    for (asset, buckets) in grouped {
+        let existing: Vec<Rollup> = env
+            .read_filter()
+            .column_equal_to("asset", asset.to_string())
+            .column_equal_to("protocol", protocol.to_string())
+            .read()
+            .unwrap();
+        let mut tvl = existing
+            .iter()
+            .max_by_key(|row| row.bucket)
+            .map(|row| row.tvl)
+            .unwrap_or(0);
+
+        for (bucket, (volume, tvl_delta)) in buckets {
+            tvl += tvl_delta;
+
+            if let Some(row) = existing.iter().find(|row| row.bucket == bucket) {
+                let row = Rollup {
+                    volume: row.volume + volume,
+                    tvl,
+                    ..row.clone()
+                };
+                env.update()
+                    .column_equal_to("asset", asset.to_string())
+                    .column_equal_to("protocol", protocol.to_string())
+                    .column_equal_to("bucket", bucket)
+                    .execute(&row)
+                    .unwrap();
+            } else {
+                env.put(&Rollup {
+                    asset: asset.to_string(),
+                    protocol: protocol.to_string(),
+                    bucket,
+                    volume,
+                    tvl,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use zephyr_sdk::EnvClient;
+
+/// Formats a raw token amount as an exact decimal string.
+pub fn format_amount(amount: i128, decimals: u32) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    let digits = amount.unsigned_abs().to_string();
+    let decimals = decimals as usize;
+
+    if decimals == 0 {
+        return format!("{}{}", sign, digits);
+    }
+
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+/// Scales a raw token amount to whole units for charting, as the chart
+/// series only take integers. Only for display, the fraction is dropped.
+pub fn to_units(amount: i128, decimals: u32) -> i64 {
+    10_i128
+        .checked_pow(decimals)
+        .map_or(0, |scale| (amount / scale) as i64)
+}
+
+/// Formats a unix timestamp as an ISO 8601 date (`2024-05-01`).
+pub fn iso_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats a unix timestamp as an ISO 8601 date and hour (`2024-05-01T13:00Z`).
+pub fn iso_hour(timestamp: u64) -> String {
+    format!(
+        "{}T{:02}:00Z",
+        iso_date(timestamp),
+        (timestamp % 86_400) / 3_600
+    )
+}
+
+/// Reads an optional JSON request body, `T::default()` when it's `null`.
+/// Errors when the body doesn't match `T`.
+pub fn read_request_or_default<T: DeserializeOwned + Default>(env: &EnvClient) -> Result<T, String> {
+    parse_request(env.read_request_body())
+}
+
+fn parse_request<T: DeserializeOwned + Default>(body: Option<Value>) -> Result<T, String> {
+    body.map(serde_json::from_value)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(|error| format!("invalid request: {}", error))
+}
+
+// Howard Hinnant's days-to-civil algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[test]
+fn formats_dates() {
+    assert_eq!(iso_date(0), "1970-01-01");
+    assert_eq!(iso_date(951_782_400), "2000-02-29");
+    assert_eq!(iso_date(1_714_521_600), "2024-05-01");
+    assert_eq!(iso_hour(1_714_570_200), "2024-05-01T13:00Z");
+}
+
+#[test]
+fn formats_amounts() {
+    assert_eq!(format_amount(0, 7), "0");
+    assert_eq!(format_amount(10_000_000, 7), "1");
+    assert_eq!(format_amount(12_345_678, 7), "1.2345678");
+    assert_eq!(format_amount(-5_000, 7), "-0.0005");
+    assert_eq!(format_amount(1_500, 2), "15");
+    assert_eq!(format_amount(42, 0), "42");
+    assert_eq!(
+        format_amount(i128::MAX, 18),
+        "170141183460469231731.687303715884105727"
+    );
+}
+
+#[test]
+fn parses_requests() {
+    assert_eq!(parse_request::<Vec<u32>>(None), Ok(vec![]));
+    assert_eq!(parse_request::<Vec<u32>>(Some(serde_json::json!([1, 2]))), Ok(vec![1, 2]));
+    assert!(parse_request::<Vec<u32>>(Some(serde_json::json!({ "resolution": "daily" }))).is_err());
+}